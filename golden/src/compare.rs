@@ -0,0 +1,108 @@
+use image::{Rgba, RgbaImage};
+
+/// How far a rendered image may be off from its reference
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Largest per-channel difference that is ignored completely
+    pub per_channel: u8,
+    /// Perceptual (YIQ) color difference in `0.0..=1.0` above which a pixel counts as differing
+    pub perceptual: f32,
+    /// Fraction of pixels that may differ before the comparison fails
+    pub max_differing_ratio: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            per_channel: 2,
+            perceptual: 0.05,
+            max_differing_ratio: 0.001,
+        }
+    }
+}
+
+/// Outcome of comparing two images of the same size
+#[derive(Debug)]
+pub struct Comparison {
+    pub differing_pixels: u64,
+    pub total_pixels: u64,
+    /// Dimmed grayscale of the reference with differing pixels in red
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    pub fn differing_ratio(&self) -> f32 {
+        self.differing_pixels as f32 / self.total_pixels as f32
+    }
+
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.differing_ratio() <= tolerance.max_differing_ratio
+    }
+}
+
+/// Compares two images pixel by pixel, both have to be the same size
+pub fn compare(actual: &RgbaImage, reference: &RgbaImage, tolerance: &Tolerance) -> Comparison {
+    debug_assert_eq!(actual.dimensions(), reference.dimensions());
+
+    let mut diff = RgbaImage::new(reference.width(), reference.height());
+    let mut differing_pixels = 0;
+
+    for ((a, r), d) in actual
+        .pixels()
+        .zip(reference.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let max_channel_delta =
+            a.0.iter()
+                .zip(r.0.iter())
+                .map(|(a, r)| a.abs_diff(*r))
+                .max()
+                .unwrap_or(0);
+
+        let differs = max_channel_delta > tolerance.per_channel
+            && perceptual_delta(a, r) > tolerance.perceptual;
+
+        *d = if differs {
+            differing_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let gray = (luma(r) * 0.25 * 255.0) as u8;
+            Rgba([gray, gray, gray, 255])
+        };
+    }
+
+    Comparison {
+        differing_pixels,
+        total_pixels: reference.width() as u64 * reference.height() as u64,
+        diff,
+    }
+}
+
+/// Perceptual color difference in YIQ space, normalized to `0.0..=1.0`
+///
+/// Colors are blended onto white first, so fully transparent pixels compare equal.
+fn perceptual_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    // Largest possible delta of the weighted YIQ distance below
+    const MAX_DELTA: f32 = 35215.0;
+
+    let [ay, ai, aq] = yiq(a);
+    let [by, bi, bq] = yiq(b);
+    let (dy, di, dq) = (ay - by, ai - bi, aq - bq);
+
+    ((0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq) / MAX_DELTA).sqrt()
+}
+
+fn yiq(pixel: &Rgba<u8>) -> [f32; 3] {
+    let alpha = pixel.0[3] as f32 / 255.0;
+    let [r, g, b] = [0, 1, 2].map(|i| 255.0 + (pixel.0[i] as f32 - 255.0) * alpha);
+
+    [
+        r * 0.298_895 + g * 0.586_622 + b * 0.114_482,
+        r * 0.595_978 - g * 0.274_176 - b * 0.321_802,
+        r * 0.211_470 - g * 0.522_617 + b * 0.311_146,
+    ]
+}
+
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    yiq(pixel)[0] / 255.0
+}
@@ -0,0 +1,188 @@
+//! Golden image tests for renderer scenes
+//!
+//! Scenes get rendered offscreen on a software adapter and compared to
+//! reference PNGs. Run with `GOLDEN_UPDATE=1` to (re)write the references.
+
+mod compare;
+
+pub use compare::{compare, Comparison, Tolerance};
+
+use renderer::wgpu::{WgpuContext, WgpuContextBuilder, WgpuError};
+use std::path::{Path, PathBuf};
+
+/// Environment variable that makes [`GoldenHarness::check`] write references instead of comparing
+pub const UPDATE_ENV: &str = "GOLDEN_UPDATE";
+
+#[derive(Debug, thiserror::Error)]
+pub enum GoldenError {
+    #[error(transparent)]
+    Wgpu(#[from] WgpuError),
+
+    #[error("failed to read or write {path}: {source}")]
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+
+    #[error("failed to create directory {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("no reference image at {0}, run with {UPDATE_ENV}=1 to create it")]
+    MissingReference(PathBuf),
+
+    #[error("scene {name} rendered at {actual:?}, but reference is {reference:?}")]
+    SizeMismatch {
+        name: String,
+        actual: (u32, u32),
+        reference: (u32, u32),
+    },
+
+    #[error("scene {name} differs from reference in {differing} of {total} pixels, diff written to {diff_path}")]
+    Mismatch {
+        name: String,
+        differing: u64,
+        total: u64,
+        diff_path: PathBuf,
+    },
+}
+
+/// Renders named scenes into a fixed-size offscreen target and compares them to references
+pub struct GoldenHarness {
+    ctx: WgpuContext,
+    reference_dir: PathBuf,
+    output_dir: PathBuf,
+    tolerance: Tolerance,
+}
+
+impl GoldenHarness {
+    pub const WIDTH: u32 = 256;
+    pub const HEIGHT: u32 = 256;
+
+    pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 1.0,
+    };
+
+    /// Creates a harness on a software adapter
+    ///
+    /// References are read from `reference_dir`, actual and diff images of
+    /// failing scenes get written to `output_dir`.
+    pub fn new(
+        reference_dir: impl Into<PathBuf>,
+        output_dir: impl Into<PathBuf>,
+    ) -> Result<Self, GoldenError> {
+        let ctx = beul::execute(
+            WgpuContextBuilder::new()
+                .force_fallback_adapter(true)
+                .build_headless(Self::WIDTH, Self::HEIGHT),
+        )?;
+        log::info!("golden harness running on {:?}", ctx.adapter().get_info());
+
+        Ok(Self {
+            ctx,
+            reference_dir: reference_dir.into(),
+            output_dir: output_dir.into(),
+            tolerance: Tolerance::default(),
+        })
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Tolerance) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn ctx(&self) -> &WgpuContext {
+        &self.ctx
+    }
+
+    /// Sets up a scene with `setup`, draws it with `draw` and compares the
+    /// result to `<reference_dir>/<name>.png`
+    pub fn check<S>(
+        &self,
+        name: &str,
+        setup: impl FnOnce(&WgpuContext) -> Result<S, WgpuError>,
+        draw: impl for<'a> Fn(&'a S, &mut wgpu::RenderPass<'a>),
+    ) -> Result<(), GoldenError> {
+        let scene = setup(&self.ctx)?;
+        let actual = self.render(&scene, draw)?;
+
+        let reference_path = self.reference_dir.join(format!("{name}.png"));
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            create_dir(&self.reference_dir)?;
+            save(&actual, &reference_path)?;
+            log::info!("updated reference {}", reference_path.display());
+            return Ok(());
+        }
+
+        if !reference_path.exists() {
+            return Err(GoldenError::MissingReference(reference_path));
+        }
+        let reference = image::open(&reference_path)
+            .map_err(|source| GoldenError::Image {
+                path: reference_path.clone(),
+                source,
+            })?
+            .to_rgba8();
+
+        if actual.dimensions() != reference.dimensions() {
+            return Err(GoldenError::SizeMismatch {
+                name: name.to_string(),
+                actual: actual.dimensions(),
+                reference: reference.dimensions(),
+            });
+        }
+
+        let comparison = compare(&actual, &reference, &self.tolerance);
+        if comparison.passes(&self.tolerance) {
+            return Ok(());
+        }
+
+        create_dir(&self.output_dir)?;
+        let diff_path = self.output_dir.join(format!("{name}-diff.png"));
+        save(&actual, &self.output_dir.join(format!("{name}-actual.png")))?;
+        save(&comparison.diff, &diff_path)?;
+
+        Err(GoldenError::Mismatch {
+            name: name.to_string(),
+            differing: comparison.differing_pixels,
+            total: comparison.total_pixels,
+            diff_path,
+        })
+    }
+
+    fn render<S>(
+        &self,
+        scene: &S,
+        draw: impl for<'a> Fn(&'a S, &mut wgpu::RenderPass<'a>),
+    ) -> Result<image::RgbaImage, GoldenError> {
+        // Can't fail, headless contexts always get a frame
+        let mut frame = self.ctx.begin_frame()?.unwrap_or_else(|| unreachable!());
+
+        {
+            let mut pass =
+                frame.begin_render_pass(Some("golden render pass"), Some(Self::CLEAR_COLOR));
+            draw(scene, &mut pass);
+        }
+
+        Ok(frame.finish_and_capture()?)
+    }
+}
+
+fn create_dir(path: &Path) -> Result<(), GoldenError> {
+    std::fs::create_dir_all(path).map_err(|source| GoldenError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn save(img: &image::RgbaImage, path: &Path) -> Result<(), GoldenError> {
+    img.save(path).map_err(|source| GoldenError::Image {
+        path: path.to_path_buf(),
+        source,
+    })
+}
@@ -0,0 +1,27 @@
+use golden::{GoldenError, GoldenHarness};
+use playground::triangle::Triangle;
+use renderer::wgpu::WgpuError;
+
+/// Creates the harness, or `None` if this machine has no software adapter
+fn harness() -> Option<GoldenHarness> {
+    match GoldenHarness::new(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/references"),
+        concat!(env!("CARGO_TARGET_TMPDIR"), "/golden"),
+    ) {
+        Ok(harness) => Some(harness),
+        Err(GoldenError::Wgpu(WgpuError::NoFittingAdapterFound)) => {
+            eprintln!("no software adapter available, skipping golden test");
+            None
+        }
+        Err(e) => panic!("failed to create golden harness: {e}"),
+    }
+}
+
+#[test]
+fn triangle() {
+    let Some(harness) = harness() else { return };
+
+    if let Err(e) = harness.check("triangle", Triangle::new, Triangle::render) {
+        panic!("{e}");
+    }
+}
@@ -0,0 +1,5 @@
+fn main() {
+    shader_codegen::Generator::new("../resources/shaders")
+        .write_to_out_dir("shaders.rs")
+        .unwrap_or_else(|e| panic!("failed to generate shader types\n{}", e.report()));
+}
@@ -1,7 +1,9 @@
-use crate::triangle::Triangle;
-use renderer::wgpu::WgpuContext;
-use std::error::Error;
-use winit::event::{Event, WindowEvent};
+use playground::triangle::Triangle;
+#[cfg(feature = "hot-reload")]
+use renderer::wgpu::ShaderWatcher;
+use renderer::wgpu::{Frame, WgpuContext, WgpuError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::Window;
 
@@ -9,77 +11,118 @@ pub struct Game {
     ctx: WgpuContext,
     window: Window,
     event_loop: Option<EventLoop<()>>,
+    screenshot_requested: bool,
+    /// `None` if watching failed, the game runs without hot reloading then
+    #[cfg(feature = "hot-reload")]
+    shader_watcher: Option<ShaderWatcher>,
 
     triangle: Triangle,
 }
 
 impl Game {
-    pub fn new(event_loop: EventLoop<()>, window: Window) -> Result<Self, Box<dyn Error>> {
+    pub fn new(event_loop: EventLoop<()>, window: Window) -> Result<Self, WgpuError> {
         let ctx = beul::execute(WgpuContext::new(&window))?;
         log::info!("initialized wgpu");
 
-        let triangle = Triangle::new(&ctx);
+        #[cfg(feature = "hot-reload")]
+        let (triangle, shader_watcher) = Self::watch_shaders(&ctx)?;
+        #[cfg(not(feature = "hot-reload"))]
+        let triangle = Triangle::new(&ctx)?;
 
         Ok(Self {
             ctx,
             window,
             event_loop: Some(event_loop),
+            screenshot_requested: false,
+            #[cfg(feature = "hot-reload")]
+            shader_watcher,
 
             triangle,
         })
     }
 
-    pub fn render(&self) -> Result<(), wgpu::SurfaceError> {
-        let frame = self.ctx.surface().get_current_texture()?;
-
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder =
-            self.ctx
-                .device()
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("main command encoder"),
-                });
-
-        {
-            let mut main_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("main render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.807,
-                            g: 1.0,
-                            b: 0.101,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.ctx.depth_buffer().view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
-
-            self.triangle.render(&mut main_pass);
+    /// Loads the shaders from disk if their directory can be watched, the
+    /// embedded ones otherwise
+    #[cfg(feature = "hot-reload")]
+    fn watch_shaders(ctx: &WgpuContext) -> Result<(Triangle, Option<ShaderWatcher>), WgpuError> {
+        let root = playground::shader_root();
+        match ShaderWatcher::new(&root) {
+            Ok(watcher) => Ok((Triangle::watched(ctx, &root)?, Some(watcher))),
+            Err(e) => {
+                log::warn!(
+                    "shader hot reloading disabled, can't watch {}: {e}",
+                    root.display()
+                );
+                Ok((Triangle::new(ctx)?, None))
+            }
+        }
+    }
+
+    pub fn render(&mut self) -> Result<(), WgpuError> {
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+
+            // Surface textures can't be read back, so the screenshot gets its own frame
+            let mut capture = self.ctx.begin_capture_frame()?;
+            self.draw(&mut capture);
+            Self::save_screenshot(capture.finish_and_capture());
         }
 
-        self.ctx.queue().submit(Some(encoder.finish()));
-        frame.present();
+        let Some(mut frame) = self.ctx.begin_frame()? else {
+            return Ok(());
+        };
+        self.draw(&mut frame);
+        frame.finish();
 
         Ok(())
     }
 
+    fn draw(&self, frame: &mut Frame) {
+        let mut main_pass = frame.begin_render_pass(
+            Some("main render pass"),
+            Some(wgpu::Color {
+                r: 0.807,
+                g: 1.0,
+                b: 0.101,
+                a: 1.0,
+            }),
+        );
+
+        self.triangle.render(&mut main_pass);
+    }
+
+    fn save_screenshot(capture: Result<image::RgbaImage, WgpuError>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = format!("screenshot_{timestamp}.png");
+
+        match capture {
+            Ok(img) => match img.save(&path) {
+                Ok(_) => log::info!("saved screenshot to {path}"),
+                Err(e) => log::error!("failed to save screenshot to {path}: {e}"),
+            },
+            Err(e) => log::error!("failed to read back frame: {e}"),
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &self.shader_watcher else {
+            return;
+        };
+
+        let changed = watcher.changed_paths();
+        if !changed.is_empty() {
+            self.triangle.reload_changed(&self.ctx, &changed);
+        }
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        self.ctx.resize(new_size);
+        if let Err(e) = self.ctx.resize(new_size) {
+            log::error!("failed to resize to {new_size:?}: {e}");
+        }
     }
 
     pub fn run(mut self) {
@@ -96,21 +139,26 @@ impl Game {
                     new_inner_size,
                 } => self.resize(*new_inner_size),
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F12),
+                            ..
+                        },
+                    ..
+                } => self.screenshot_requested = true,
                 _ => {}
             },
-            Event::RedrawRequested(_) => match self.render() {
-                Ok(_) => {}
-                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                    // Re-create surface on lost|outdated
-                    self.resize(self.ctx.surface_size());
-                }
-                Err(wgpu::SurfaceError::OutOfMemory) => {
-                    log::error!("out of (gpu?) memory");
+            Event::RedrawRequested(_) => {
+                if let Err(e) = self.render() {
+                    log::error!("failed to render frame: {e}");
                     *control_flow = ControlFlow::Exit;
                 }
-                Err(wgpu::SurfaceError::Timeout) => log::warn!("surface timout, ignoring..."),
-            },
+            }
             Event::MainEventsCleared => {
+                #[cfg(feature = "hot-reload")]
+                self.reload_shaders();
                 self.window.request_redraw();
             }
             _ => {}
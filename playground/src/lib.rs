@@ -0,0 +1,15 @@
+/// Rust types and binding constants generated from `resources/shaders`
+pub mod shaders {
+    include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+}
+pub mod triangle;
+
+/// Where the shaders are watched for hot reloading
+///
+/// `PLAYGROUND_SHADER_ROOT` if set, `resources/shaders` below the working directory otherwise.
+#[cfg(feature = "hot-reload")]
+pub fn shader_root() -> std::path::PathBuf {
+    std::env::var_os("PLAYGROUND_SHADER_ROOT")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::PathBuf::from("resources/shaders"))
+}
@@ -1,7 +1,8 @@
 mod game;
-mod triangle;
 
 use game::Game;
+use renderer::wgpu::WgpuError;
+use std::error::Error;
 use winit::event_loop::EventLoop;
 use winit::window::WindowBuilder;
 
@@ -13,20 +14,31 @@ fn main() {
 
     env_logger::init();
 
-    let mut game = setup();
+    let game = match setup() {
+        Ok(game) => game,
+        Err(e) => {
+            match e
+                .downcast_ref::<WgpuError>()
+                .and_then(WgpuError::shader_diagnostic)
+            {
+                Some(diagnostic) => log::error!("failed to set up game\n{}", diagnostic.render()),
+                None => log::error!("failed to set up game: {e}"),
+            }
+            std::process::exit(1);
+        }
+    };
 
     game.run();
 }
 
-fn setup() -> Game {
+fn setup() -> Result<Game, Box<dyn Error>> {
     let event_loop = EventLoop::new();
 
     let window = WindowBuilder::new()
         .with_title("WGPU Playground")
         .with_fullscreen(None)
         .with_resizable(true)
-        .build(&event_loop)
-        .unwrap();
+        .build(&event_loop)?;
 
-    Game::new(event_loop, window).unwrap()
+    Ok(Game::new(event_loop, window)?)
 }
@@ -1,8 +1,15 @@
 use bytemuck::{Pod, Zeroable};
-use renderer::wgpu::{Buffer, RenderPipeline, ShaderSource, Vertex, WgpuContext};
+#[cfg(feature = "hot-reload")]
+use renderer::wgpu::{Preprocessor, ReloadablePipeline};
+use renderer::wgpu::{
+    RenderPipeline, RenderPipelineBuilder, ShaderSource, TypedBuffer, Vertex, WgpuContext,
+    WgpuError,
+};
+#[cfg(feature = "hot-reload")]
+use std::path::{Path, PathBuf};
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable, Vertex)]
 pub struct TriangleVertex {
     position: [f32; 3],
     uv: [f32; 2],
@@ -10,8 +17,26 @@ pub struct TriangleVertex {
 }
 
 pub struct Triangle {
-    pipeline: RenderPipeline,
-    vtx_buf: Buffer,
+    pipeline: TrianglePipeline,
+    vtx_buf: TypedBuffer<TriangleVertex>,
+}
+
+enum TrianglePipeline {
+    /// Built once from the shader compiled into the binary
+    Embedded(RenderPipeline),
+    /// Built from the shader file, rebuilt when it changes
+    #[cfg(feature = "hot-reload")]
+    Watched(Box<ReloadablePipeline<'static>>),
+}
+
+impl TrianglePipeline {
+    fn get(&self) -> &RenderPipeline {
+        match self {
+            Self::Embedded(pipeline) => pipeline,
+            #[cfg(feature = "hot-reload")]
+            Self::Watched(pipeline) => pipeline.pipeline(),
+        }
+    }
 }
 
 impl Triangle {
@@ -22,39 +47,73 @@ impl Triangle {
         TriangleVertex::new([ 0.5, -0.5, 0.0], [0.0; 2], [0.0, 0.0, 1.0, 1.0]),
     ];
 
-    pub fn new(ctx: &WgpuContext) -> Self {
-        let pipeline = RenderPipeline::new(
+    const SHADER: &'static str = include_str!("../../resources/shaders/simple_triangle.wgsl");
+
+    /// Uses the shader compiled into the binary
+    pub fn new(ctx: &WgpuContext) -> Result<Self, WgpuError> {
+        let pipeline =
+            Self::pipeline_builder().build(ctx, ShaderSource::SourceCode(Self::SHADER))?;
+        Self::with_pipeline(ctx, TrianglePipeline::Embedded(pipeline))
+    }
+
+    /// Loads the shader from `shader_root`, so it can be reloaded when it changes
+    #[cfg(feature = "hot-reload")]
+    pub fn watched(ctx: &WgpuContext, shader_root: &Path) -> Result<Self, WgpuError> {
+        let pipeline = ReloadablePipeline::new(
             ctx,
-            ShaderSource::SourceCode(include_str!("../../resources/shaders/simple_triangle.wgsl")),
-            &[TriangleVertex::desc()],
-            Some("simple triangle pipeline"),
-        );
+            Preprocessor::new(shader_root),
+            "simple_triangle.wgsl",
+            Self::pipeline_builder(),
+        )?;
+        Self::with_pipeline(ctx, TrianglePipeline::Watched(Box::new(pipeline)))
+    }
 
-        let vtx_buf = Buffer::new_init(
+    fn pipeline_builder() -> RenderPipelineBuilder<'static> {
+        RenderPipeline::builder()
+            .label("simple triangle pipeline")
+            .vertex_buffers(&[TriangleVertex::desc()])
+    }
+
+    fn with_pipeline(ctx: &WgpuContext, pipeline: TrianglePipeline) -> Result<Self, WgpuError> {
+        let vtx_buf = TypedBuffer::new_init(
             ctx.device(),
-            bytemuck::cast_slice(Self::VERTICES),
+            Self::VERTICES,
             wgpu::BufferUsages::VERTEX,
             Some("triangle vertex buffer"),
-        );
+        )?;
 
-        Self { pipeline, vtx_buf }
+        Ok(Self { pipeline, vtx_buf })
+    }
+
+    /// Rebuilds the pipeline if its shader is among the changed files
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_changed(&mut self, ctx: &WgpuContext, changed: &[PathBuf]) {
+        if let TrianglePipeline::Watched(pipeline) = &mut self.pipeline {
+            match pipeline.reload_if_changed(ctx, changed) {
+                Ok(true) => log::info!("reloaded shader {}", pipeline.path().display()),
+                Ok(false) => {}
+                Err(e) => match e.shader_diagnostic() {
+                    Some(diagnostic) => log::error!(
+                        "failed to reload shader, keeping the last good pipeline\n{}",
+                        diagnostic.render()
+                    ),
+                    None => {
+                        log::error!("failed to reload shader, keeping the last good pipeline: {e}")
+                    }
+                },
+            }
+        }
     }
 
     pub fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
-        rpass.set_pipeline(self.pipeline.raw());
+        rpass.set_pipeline(self.pipeline.get().raw());
         rpass.set_vertex_buffer(0, self.vtx_buf.raw().slice(..));
 
-        rpass.draw(0..3, 0..1);
+        rpass.draw(0..self.vtx_buf.len() as u32, 0..1);
     }
 }
 
 impl TriangleVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array! {
-        0 => Float32x3,
-        1 => Float32x2,
-        2 => Float32x4,
-    };
-
     pub const fn new(position: [f32; 3], uv: [f32; 2], color: [f32; 4]) -> Self {
         Self {
             position,
@@ -63,13 +122,3 @@ impl TriangleVertex {
         }
     }
 }
-
-impl Vertex for TriangleVertex {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<TriangleVertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
-        }
-    }
-}
@@ -1 +1,8 @@
 pub mod wgpu;
+
+/// Used by the code generated by `renderer_derive`
+#[doc(hidden)]
+pub mod __private {
+    pub use bytemuck;
+    pub use wgpu;
+}
@@ -1,49 +1,70 @@
+use crate::wgpu::scope;
+use crate::wgpu::{BufferError, WgpuContext, WgpuError};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
 use wgpu::util::DeviceExt;
 
 pub struct Buffer {
     raw: wgpu::Buffer,
+    label: Option<String>,
     size: wgpu::BufferAddress,
     usages: wgpu::BufferUsages,
 }
 
 impl Buffer {
+    /// Creates a buffer of at least `size` bytes
+    ///
+    /// The size is rounded up to whole words, like `create_buffer_init` does,
+    /// since copies and mappings can only cover whole words.
     pub fn new(
         device: &wgpu::Device,
         size: wgpu::BufferAddress,
         usages: wgpu::BufferUsages,
         mapped: bool,
         label: Option<&str>,
-    ) -> Self {
-        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label,
-            size,
-            usage: usages,
-            mapped_at_creation: mapped,
-        });
-        Self {
+    ) -> Result<Self, WgpuError> {
+        // Sizes this close to the limit fail in wgpu anyway
+        let size = size
+            .checked_next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+            .unwrap_or(size);
+        let buffer = scope::capture(device, label, || {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label,
+                size,
+                usage: usages,
+                mapped_at_creation: mapped,
+            })
+        })?;
+        Ok(Self {
             raw: buffer,
+            label: label.map(str::to_string),
             size,
             usages,
-        }
+        })
     }
 
-    pub fn new_init<'a>(
+    pub fn new_init(
         device: &wgpu::Device,
-        contents: &'a [u8],
+        contents: &[u8],
         usages: wgpu::BufferUsages,
         label: Option<&str>,
-    ) -> Self {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label,
-            contents,
-            usage: usages,
-        });
+    ) -> Result<Self, WgpuError> {
+        let buffer = scope::capture(device, label, || {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label,
+                contents,
+                usage: usages,
+            })
+        })?;
         let size = buffer.size();
-        Self {
+        Ok(Self {
             raw: buffer,
+            label: label.map(str::to_string),
             size,
             usages,
-        }
+        })
     }
 
     pub fn size(&self) -> wgpu::BufferAddress {
@@ -54,7 +75,220 @@ impl Buffer {
         self.usages
     }
 
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
     pub fn raw(&self) -> &wgpu::Buffer {
         &self.raw
     }
+
+    /// Binds the whole buffer at `binding` of a bind group
+    pub fn bind_group_entry(&self, binding: u32) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: self.raw.as_entire_binding(),
+        }
+    }
+
+    /// Schedules writing `data` at `offset`, it lands before the next submission
+    ///
+    /// The buffer needs [`wgpu::BufferUsages::COPY_DST`], and `offset` and the
+    /// length of `data` have to be multiples of [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub fn write(
+        &self,
+        queue: &wgpu::Queue,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) -> Result<(), WgpuError> {
+        self.check_write(offset, data.len() as wgpu::BufferAddress)?;
+
+        if !data.is_empty() {
+            queue.write_buffer(&self.raw, offset, data);
+        }
+        Ok(())
+    }
+
+    /// Copies the buffer's contents back to the CPU, resolving once the GPU is done
+    ///
+    /// Buffers with [`wgpu::BufferUsages::MAP_READ`] are mapped directly, all
+    /// others need [`wgpu::BufferUsages::COPY_SRC`] to be copied into a staging
+    /// buffer first. Trailing bytes that don't fill a whole `T` are left out.
+    ///
+    /// The future polls the device itself, without blocking the first time it
+    /// is polled and waiting for the GPU the second time, so it also completes
+    /// under executors that only poll futures once they are woken, like
+    /// `beul::execute`.
+    pub async fn read_async<T: bytemuck::Pod>(
+        &self,
+        ctx: &WgpuContext,
+    ) -> Result<Vec<T>, WgpuError> {
+        self.read_with(ctx, wgpu::Maintain::Poll).await
+    }
+
+    /// Like [`Buffer::read_async`], blocking until the data is there
+    pub fn read<T: bytemuck::Pod>(&self, ctx: &WgpuContext) -> Result<Vec<T>, WgpuError> {
+        beul::execute(self.read_with(ctx, wgpu::Maintain::Wait))
+    }
+
+    async fn read_with<T: bytemuck::Pod>(
+        &self,
+        ctx: &WgpuContext,
+        maintain: wgpu::Maintain,
+    ) -> Result<Vec<T>, WgpuError> {
+        let element_size = std::mem::size_of::<T>() as wgpu::BufferAddress;
+        let len = match element_size {
+            0 => 0,
+            size => self.size / size,
+        };
+        let size = len * element_size;
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Mapping and copying work in whole words, the buffer size is rounded up to them
+        let mapped_size = size.div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT;
+
+        let staging = match self.usages.contains(wgpu::BufferUsages::MAP_READ) {
+            true => None,
+            false => Some(self.copy_to_staging(ctx, mapped_size)?),
+        };
+        let target = staging.as_ref().unwrap_or(&self.raw);
+        let slice = target.slice(..mapped_size);
+
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let callback_state = state.clone();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let mut state = callback_state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        MapFuture {
+            device: ctx.device(),
+            maintain,
+            state,
+        }
+        .await
+        .map_err(|source| BufferError::Map {
+            label: self.label.clone(),
+            source,
+        })?;
+
+        let mut data = vec![T::zeroed(); len as usize];
+        bytemuck::cast_slice_mut(&mut data)
+            .copy_from_slice(&slice.get_mapped_range()[..size as usize]);
+        target.unmap();
+
+        Ok(data)
+    }
+
+    fn copy_to_staging(
+        &self,
+        ctx: &WgpuContext,
+        size: wgpu::BufferAddress,
+    ) -> Result<wgpu::Buffer, WgpuError> {
+        self.check_usage(wgpu::BufferUsages::COPY_SRC)?;
+
+        let staging = ctx.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("buffer readback buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = ctx
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("buffer readback encoder"),
+            });
+        encoder.copy_buffer_to_buffer(&self.raw, 0, &staging, 0, size);
+        ctx.queue().submit(Some(encoder.finish()));
+
+        Ok(staging)
+    }
+
+    /// Makes sure `size` bytes can be copied into the buffer at `offset`
+    pub(crate) fn check_write(
+        &self,
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+    ) -> Result<(), BufferError> {
+        self.check_usage(wgpu::BufferUsages::COPY_DST)?;
+
+        if offset.checked_add(size).map_or(true, |end| end > self.size) {
+            return Err(BufferError::OutOfBounds {
+                label: self.label.clone(),
+                offset,
+                size,
+                buffer_size: self.size,
+            });
+        }
+        if offset % wgpu::COPY_BUFFER_ALIGNMENT != 0 || size % wgpu::COPY_BUFFER_ALIGNMENT != 0 {
+            return Err(BufferError::Unaligned {
+                label: self.label.clone(),
+                offset,
+                size,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn check_usage(&self, usage: wgpu::BufferUsages) -> Result<(), BufferError> {
+        match self.usages.contains(usage) {
+            true => Ok(()),
+            false => Err(BufferError::MissingUsage {
+                label: self.label.clone(),
+                usage,
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+/// Waits for a `map_async` callback, polling the device every time it is polled itself
+///
+/// The first poll doesn't block. If the GPU isn't done by then, the future
+/// asks to be polled once more and waits for the GPU in that poll.
+struct MapFuture<'a> {
+    device: &'a wgpu::Device,
+    maintain: wgpu::Maintain,
+    state: Arc<Mutex<MapState>>,
+}
+
+impl<'a> Future for MapFuture<'a> {
+    type Output = Result<(), wgpu::BufferAsyncError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Runs the callback if the GPU is done, with Maintain::Wait after waiting for it
+        self.device.poll(self.maintain.clone());
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                drop(state);
+
+                // Nothing else polls the device, so the callback could never run
+                // unless the executor comes back here. Waiting then instead of
+                // polling again keeps the future from spinning.
+                if matches!(self.maintain, wgpu::Maintain::Poll) {
+                    self.maintain = wgpu::Maintain::Wait;
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+        }
+    }
 }
@@ -0,0 +1,220 @@
+use crate::wgpu::{reflection, scope};
+use crate::wgpu::{PipelineError, Shader, ShaderSource, WgpuContext, WgpuError};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub struct ComputePipeline {
+    raw: wgpu::ComputePipeline,
+    layout: wgpu::PipelineLayout,
+    bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    shader: Arc<Shader>,
+    entry_point: String,
+    workgroup_size: [u32; 3],
+}
+
+impl ComputePipeline {
+    /// Creates a pipeline with the default settings of [`ComputePipelineBuilder`]
+    pub fn new(
+        ctx: &WgpuContext,
+        shader: ShaderSource,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let mut builder = ComputePipelineBuilder::new();
+        if let Some(label) = label {
+            builder = builder.label(label);
+        }
+
+        builder.build(ctx, shader)
+    }
+
+    pub fn builder<'a>() -> ComputePipelineBuilder<'a> {
+        ComputePipelineBuilder::new()
+    }
+
+    pub fn raw(&self) -> &wgpu::ComputePipeline {
+        &self.raw
+    }
+
+    pub fn layout(&self) -> &wgpu::PipelineLayout {
+        &self.layout
+    }
+
+    /// Bind group layouts generated from the shader's reflection, indexed by group
+    ///
+    /// Empty if the layouts were given explicitly to the builder.
+    pub fn bind_group_layouts(&self) -> &[wgpu::BindGroupLayout] {
+        &self.bind_group_layouts
+    }
+
+    pub fn bind_group_layout(&self, group: u32) -> Option<&wgpu::BindGroupLayout> {
+        self.bind_group_layouts.get(group as usize)
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    pub fn entry_point(&self) -> &str {
+        &self.entry_point
+    }
+
+    /// `@workgroup_size` of the entry point
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// Number of workgroups needed to cover `extent` invocations on each axis
+    pub fn workgroup_count(&self, extent: [u32; 3]) -> [u32; 3] {
+        let [x, y, z] = self.workgroup_size;
+        [
+            extent[0].div_ceil(x),
+            extent[1].div_ceil(y),
+            extent[2].div_ceil(z),
+        ]
+    }
+
+    /// Sets the pipeline and dispatches one invocation per element along x
+    ///
+    /// The last workgroup can run past `elements`, the shader has to check the
+    /// invocation id against the length of its data. Bind groups have to be set
+    /// on the pass before.
+    pub fn dispatch<'a>(&'a self, pass: &mut wgpu::ComputePass<'a>, elements: u32) {
+        self.dispatch_3d(pass, [elements, 1, 1]);
+    }
+
+    /// Like [`ComputePipeline::dispatch`], with one invocation per texel of a 2D extent
+    pub fn dispatch_2d<'a>(&'a self, pass: &mut wgpu::ComputePass<'a>, width: u32, height: u32) {
+        self.dispatch_3d(pass, [width, height, 1]);
+    }
+
+    /// Like [`ComputePipeline::dispatch`], with one invocation per cell of a 3D extent
+    pub fn dispatch_3d<'a>(&'a self, pass: &mut wgpu::ComputePass<'a>, extent: [u32; 3]) {
+        let [x, y, z] = self.workgroup_count(extent);
+        pass.set_pipeline(&self.raw);
+        pass.dispatch_workgroups(x, y, z);
+    }
+}
+
+/// Configures a [`ComputePipeline`]
+///
+/// Uses the shader's default compute entry point unless another one is
+/// picked by name. Unless bind group layouts are set explicitly, they are
+/// generated from the shader's reflection, like for
+/// [`RenderPipelineBuilder`](crate::wgpu::RenderPipelineBuilder).
+#[derive(Debug, Clone, Default)]
+pub struct ComputePipelineBuilder<'a> {
+    label: Option<String>,
+    entry_point: Option<String>,
+    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    binding_types: BTreeMap<(u32, u32), wgpu::BindingType>,
+    workgroup_size: Option<[u32; 3]>,
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Compute entry point, [`Shader::compute_entry`] if not set
+    pub fn entry_point(mut self, name: impl Into<String>) -> Self {
+        self.entry_point = Some(name.into());
+        self
+    }
+
+    /// Overrides the layouts generated from the shader's reflection
+    pub fn bind_group_layouts(mut self, layouts: &[&'a wgpu::BindGroupLayout]) -> Self {
+        self.bind_group_layouts = layouts.to_vec();
+        self
+    }
+
+    /// Replaces the reflected type of a binding in the generated layouts
+    ///
+    /// Reflection can't tell the texture format or how a sampler is used, so it
+    /// assumes filterable float textures and filtering samplers. E.g. an
+    /// `r32float` texture needs `Float { filterable: false }` here, and the
+    /// sampler used with it [`wgpu::SamplerBindingType::NonFiltering`].
+    pub fn binding_type(mut self, group: u32, binding: u32, ty: wgpu::BindingType) -> Self {
+        self.binding_types.insert((group, binding), ty);
+        self
+    }
+
+    /// Workgroup size for shaders without reflection, i.e. raw [`wgpu::ShaderModule`]s
+    ///
+    /// Ignored if the size can be read from the shader, `[1, 1, 1]` if neither is known.
+    /// Building fails if a component is zero.
+    pub fn workgroup_size(mut self, size: [u32; 3]) -> Self {
+        self.workgroup_size = Some(size);
+        self
+    }
+
+    pub fn build(
+        &self,
+        ctx: &WgpuContext,
+        shader: ShaderSource,
+    ) -> Result<ComputePipeline, WgpuError> {
+        let label = self.label.as_deref();
+        let shader_label = label.map(|lbl| format!("shader for pipeline {lbl}"));
+        let shader = shader.into_shader(ctx.device(), shader_label.as_deref())?;
+
+        let entry_point = self
+            .entry_point
+            .as_deref()
+            .unwrap_or(shader.compute_entry())
+            .to_string();
+        shader.check_entry_point(&entry_point, naga::ShaderStage::Compute)?;
+
+        let workgroup_size = shader
+            .reflection()
+            .and_then(|reflection| reflection.entry_point(&entry_point))
+            .map(|ep| ep.workgroup_size)
+            .or(self.workgroup_size)
+            .unwrap_or([1, 1, 1]);
+        if workgroup_size.contains(&0) {
+            return Err(PipelineError::InvalidWorkgroupSize {
+                label: self.label.clone(),
+                size: workgroup_size,
+            }
+            .into());
+        }
+
+        let (bind_group_layouts, layout, pipeline) = scope::capture(ctx.device(), label, || {
+            let reflected_entry_point = shader
+                .reflection()
+                .map(|reflection| [(reflection, entry_point.as_str())]);
+            let (bind_group_layouts, layout) = reflection::pipeline_layout(
+                ctx.device(),
+                reflected_entry_point
+                    .as_ref()
+                    .map(|entry_points| &entry_points[..]),
+                &self.bind_group_layouts,
+                &self.binding_types,
+                "compute pipeline layout",
+            )?;
+
+            let pipeline = ctx
+                .device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label.unwrap_or("compute pipeline")),
+                    layout: Some(&layout),
+                    module: shader.raw(),
+                    entry_point: &entry_point,
+                });
+
+            Ok::<_, PipelineError>((bind_group_layouts, layout, pipeline))
+        })??;
+
+        Ok(ComputePipeline {
+            raw: pipeline,
+            layout,
+            bind_group_layouts,
+            shader,
+            entry_point,
+            workgroup_size,
+        })
+    }
+}
@@ -1,14 +1,24 @@
-use crate::wgpu::{Texture, WgpuError};
-use std::error::Error;
+use crate::wgpu::scope;
+use crate::wgpu::{Frame, Texture, WgpuContextBuilder, WgpuError};
 use winit::window::Window;
 
+/// What the context renders into
+pub(super) enum RenderTarget {
+    /// Swapchain of a window
+    Surface {
+        surface: wgpu::Surface,
+        capabilities: wgpu::SurfaceCapabilities,
+        config: wgpu::SurfaceConfiguration,
+    },
+    /// Owned texture, used when running without a window
+    Offscreen { texture: Texture },
+}
+
 pub struct WgpuContext {
     device: wgpu::Device,
     adapter: wgpu::Adapter,
     queue: wgpu::Queue,
-    surface: wgpu::Surface,
-    surface_capabilities: wgpu::SurfaceCapabilities,
-    surface_config: wgpu::SurfaceConfiguration,
+    target: RenderTarget,
     window_size: winit::dpi::PhysicalSize<u32>,
 
     // TODO: Move this into camera probably (every camera has optional depth buffer ?)
@@ -16,88 +26,134 @@ pub struct WgpuContext {
 }
 
 impl WgpuContext {
+    /// Color format of the offscreen target of a headless context
+    pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    /// Creates a context rendering into the given window with default settings
+    ///
+    /// Use [`WgpuContextBuilder`] to configure backends, features, limits etc.
     pub async fn new(window: &Window) -> Result<Self, WgpuError> {
-        let window_size = window.inner_size();
-
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
-        });
-
-        let surface = unsafe { instance.create_surface(window) }?;
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .ok_or(WgpuError::NoFittingAdapterFound)?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("primary render device"),
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await?;
-
-        let surface_capabilities = surface.get_capabilities(&adapter);
-        let surface_format = surface_capabilities
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_capabilities.formats[0]);
-
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: window_size.width,
-            height: window_size.height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            view_formats: vec![],
-        };
+        WgpuContextBuilder::new().build(window).await
+    }
 
-        surface.configure(&device, &surface_config);
+    /// Creates a context without a window with default settings, rendering
+    /// into an owned offscreen color target of the given size.
+    ///
+    /// Falls back to a software adapter if no hardware adapter is available.
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, WgpuError> {
+        WgpuContextBuilder::new()
+            .build_headless(width, height)
+            .await
+    }
 
-        let depth_buffer =
-            Texture::create_depth_texture(&device, &surface_config, Some("main depth buffer"));
+    pub(super) fn from_parts(
+        device: wgpu::Device,
+        adapter: wgpu::Adapter,
+        queue: wgpu::Queue,
+        target: RenderTarget,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Result<Self, WgpuError> {
+        let depth_buffer = Texture::create_depth_texture(
+            &device,
+            size.width,
+            size.height,
+            Some("main depth buffer"),
+        )?;
 
         Ok(Self {
             device,
             adapter,
             queue,
-            surface,
-            surface_capabilities,
-            surface_config,
-            window_size,
+            target,
+            window_size: size,
             depth_buffer,
         })
     }
 
+    /// Runs `f` inside validation and out-of-memory error scopes, so errors
+    /// caused by it come back as [`WgpuError`] instead of going to the
+    /// uncaptured error handler
+    pub fn scoped<T>(
+        &self,
+        label: Option<&str>,
+        f: impl FnOnce(&wgpu::Device) -> T,
+    ) -> Result<T, WgpuError> {
+        scope::capture(&self.device, label, || f(&self.device))
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         &self.device
     }
 
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
     pub fn queue(&self) -> &wgpu::Queue {
         &self.queue
     }
 
-    pub fn surface(&self) -> &wgpu::Surface {
-        &self.surface
+    /// Features that were actually enabled on the device, including granted optional features
+    pub fn features(&self) -> wgpu::Features {
+        self.device.features()
+    }
+
+    /// Limits that were actually granted to the device
+    pub fn limits(&self) -> wgpu::Limits {
+        self.device.limits()
+    }
+
+    /// `true` if this context renders into an offscreen target instead of a window surface
+    pub fn is_headless(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen { .. })
+    }
+
+    /// Window surface, `None` for headless contexts
+    pub fn surface(&self) -> Option<&wgpu::Surface> {
+        match &self.target {
+            RenderTarget::Surface { surface, .. } => Some(surface),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    /// Surface configuration, `None` for headless contexts
+    pub fn surface_config(&self) -> Option<&wgpu::SurfaceConfiguration> {
+        match &self.target {
+            RenderTarget::Surface { config, .. } => Some(config),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    /// Surface capabilities, `None` for headless contexts
+    pub fn surface_capabilities(&self) -> Option<&wgpu::SurfaceCapabilities> {
+        match &self.target {
+            RenderTarget::Surface { capabilities, .. } => Some(capabilities),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    /// Offscreen color target, `None` for contexts rendering to a window
+    pub fn offscreen_target(&self) -> Option<&Texture> {
+        match &self.target {
+            RenderTarget::Surface { .. } => None,
+            RenderTarget::Offscreen { texture } => Some(texture),
+        }
     }
 
-    pub fn surface_config(&self) -> &wgpu::SurfaceConfiguration {
-        &self.surface_config
+    /// Format of the color target, either the surface or the offscreen texture
+    pub fn color_format(&self) -> wgpu::TextureFormat {
+        match &self.target {
+            RenderTarget::Surface { config, .. } => config.format,
+            RenderTarget::Offscreen { texture } => texture.raw.format(),
+        }
     }
 
-    pub fn surface_capabilities(&self) -> &wgpu::SurfaceCapabilities {
-        &self.surface_capabilities
+    /// Copies the offscreen target of a headless context into an image,
+    /// blocking until the gpu is done
+    pub fn read_offscreen_target(&self) -> Result<image::RgbaImage, WgpuError> {
+        self.offscreen_target()
+            .ok_or(WgpuError::NoOffscreenTarget)?
+            .read_to_image(&self.device, &self.queue)
     }
 
     pub fn depth_buffer(&self) -> &Texture {
@@ -108,20 +164,105 @@ impl WgpuContext {
         self.window_size
     }
 
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+    /// Acquires the next frame to render into
+    ///
+    /// Reconfigures the surface and retries once if it was lost or outdated.
+    /// Returns `None` if the frame should be skipped (timeout, or the surface
+    /// is still unusable after reconfiguring), headless contexts always get a frame.
+    pub fn begin_frame(&self) -> Result<Option<Frame<'_>>, WgpuError> {
+        let (surface, config) = match &self.target {
+            RenderTarget::Surface {
+                surface, config, ..
+            } => (surface, config),
+            RenderTarget::Offscreen { .. } => return Ok(Some(Frame::new(self, None))),
+        };
+
+        let surface_texture = match surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                log::debug!("surface lost or outdated, reconfiguring");
+                surface.configure(&self.device, config);
+
+                match surface.get_current_texture() {
+                    Ok(surface_texture) => surface_texture,
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        log::warn!("surface still unusable after reconfiguring, skipping frame");
+                        return Ok(None);
+                    }
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        log::warn!("surface timeout, skipping frame");
+                        return Ok(None);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                log::warn!("surface timeout, skipping frame");
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Some(Frame::new(self, Some(surface_texture))))
+    }
+
+    /// Begins a frame rendering into a new texture like the color target, for
+    /// reading it back with [`Frame::finish_and_capture`]
+    ///
+    /// Surface textures can't be copied from, so windowed contexts render
+    /// screenshots separately. Nothing is presented. Headless contexts get a
+    /// regular frame.
+    pub fn begin_capture_frame(&self) -> Result<Frame<'_>, WgpuError> {
+        match &self.target {
+            RenderTarget::Surface { config, .. } => {
+                let texture = Texture::create_render_target(
+                    &self.device,
+                    config.width,
+                    config.height,
+                    config.format,
+                    Some("capture color target"),
+                )?;
+                Ok(Frame::new_capture(self, texture))
+            }
+            RenderTarget::Offscreen { .. } => Ok(Frame::new(self, None)),
+        }
+    }
+
+    /// Resizes the surface or offscreen target and recreates the depth buffer,
+    /// zero-sized requests (e.g. minimized windows) are ignored
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), WgpuError> {
         if new_size.width > 0 && new_size.height > 0 {
-            self.surface_config.width = new_size.width;
-            self.surface_config.height = new_size.height;
-            self.surface.configure(&self.device, &self.surface_config);
+            match &mut self.target {
+                RenderTarget::Surface {
+                    surface, config, ..
+                } => {
+                    config.width = new_size.width;
+                    config.height = new_size.height;
+                    surface.configure(&self.device, config);
+                }
+                RenderTarget::Offscreen { texture } => {
+                    let format = texture.raw.format();
+                    *texture = Texture::create_render_target(
+                        &self.device,
+                        new_size.width,
+                        new_size.height,
+                        format,
+                        Some("offscreen color target"),
+                    )?;
+                }
+            }
 
             // recreate depth buffer
             self.depth_buffer = Texture::create_depth_texture(
-                self.device(),
-                self.surface_config(),
+                &self.device,
+                new_size.width,
+                new_size.height,
                 Some("main depth buffer"),
-            );
+            )?;
 
             self.window_size = new_size;
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,317 @@
+use crate::wgpu::context::RenderTarget;
+use crate::wgpu::{Texture, WgpuContext, WgpuError};
+use std::fmt;
+use std::sync::Arc;
+use winit::window::Window;
+
+type ErrorCallback = Arc<dyn Fn(wgpu::Error) + Send + Sync>;
+
+/// Configures and creates a [`WgpuContext`]
+///
+/// Every setting has a default matching [`WgpuContext::new`], so only what
+/// differs has to be set.
+#[derive(Clone)]
+pub struct WgpuContextBuilder {
+    backends: Option<wgpu::Backends>,
+    power_preference: wgpu::PowerPreference,
+    force_fallback_adapter: bool,
+    features: wgpu::Features,
+    optional_features: wgpu::Features,
+    limits: wgpu::Limits,
+    present_mode: wgpu::PresentMode,
+    alpha_mode: wgpu::CompositeAlphaMode,
+    on_uncaptured_error: Option<ErrorCallback>,
+}
+
+impl Default for WgpuContextBuilder {
+    fn default() -> Self {
+        Self {
+            backends: None,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            features: wgpu::Features::empty(),
+            optional_features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            on_uncaptured_error: None,
+        }
+    }
+}
+
+impl fmt::Debug for WgpuContextBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgpuContextBuilder")
+            .field("backends", &self.backends)
+            .field("power_preference", &self.power_preference)
+            .field("force_fallback_adapter", &self.force_fallback_adapter)
+            .field("features", &self.features)
+            .field("optional_features", &self.optional_features)
+            .field("limits", &self.limits)
+            .field("present_mode", &self.present_mode)
+            .field("alpha_mode", &self.alpha_mode)
+            .field("on_uncaptured_error", &self.on_uncaptured_error.is_some())
+            .finish()
+    }
+}
+
+impl WgpuContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Backends to pick an adapter from
+    ///
+    /// Defaults to [`wgpu::Backends::PRIMARY`] for windowed and
+    /// [`wgpu::Backends::all`] for headless contexts.
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = Some(backends);
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Only consider software (fallback) adapters
+    pub fn force_fallback_adapter(mut self, force: bool) -> Self {
+        self.force_fallback_adapter = force;
+        self
+    }
+
+    /// Features the device must support, creation fails if the adapter lacks any of them
+    pub fn features(mut self, features: wgpu::Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Features that get enabled only if the adapter supports them
+    ///
+    /// Check [`WgpuContext::features`] for which ones were granted.
+    pub fn optional_features(mut self, features: wgpu::Features) -> Self {
+        self.optional_features = features;
+        self
+    }
+
+    /// Limits the device must support, creation fails if the adapter can't satisfy them
+    pub fn limits(mut self, limits: wgpu::Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Preferred present mode, falls back to [`wgpu::PresentMode::AutoVsync`]
+    /// if the surface doesn't support it
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Preferred alpha mode, falls back to [`wgpu::CompositeAlphaMode::Auto`]
+    /// if the surface doesn't support it
+    pub fn alpha_mode(mut self, alpha_mode: wgpu::CompositeAlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Called for wgpu errors that happen outside of an error scope
+    ///
+    /// Resource constructors capture their own errors, this catches the rest
+    /// (e.g. invalid draw calls). Defaults to logging the error, instead of
+    /// wgpu's default of panicking.
+    pub fn on_uncaptured_error(
+        mut self,
+        callback: impl Fn(wgpu::Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_uncaptured_error = Some(Arc::new(callback));
+        self
+    }
+
+    /// Creates a context rendering into the given window
+    pub async fn build(self, window: &Window) -> Result<WgpuContext, WgpuError> {
+        let window_size = window.inner_size();
+
+        let instance = self.create_instance(wgpu::Backends::PRIMARY);
+        let surface = unsafe { instance.create_surface(window) }?;
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: self.power_preference,
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: self.force_fallback_adapter,
+            })
+            .await
+            .ok_or(WgpuError::NoFittingAdapterFound)?;
+
+        let (device, queue) = self.request_device(&adapter).await?;
+
+        let surface_capabilities = surface.get_capabilities(&adapter);
+        let surface_format = surface_capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(surface_capabilities.formats[0]);
+
+        let present_mode = if Self::is_present_mode_auto(self.present_mode)
+            || surface_capabilities
+                .present_modes
+                .contains(&self.present_mode)
+        {
+            self.present_mode
+        } else {
+            log::warn!(
+                "present mode {:?} not supported by surface, using AutoVsync",
+                self.present_mode
+            );
+            wgpu::PresentMode::AutoVsync
+        };
+
+        let alpha_mode = if self.alpha_mode == wgpu::CompositeAlphaMode::Auto
+            || surface_capabilities.alpha_modes.contains(&self.alpha_mode)
+        {
+            self.alpha_mode
+        } else {
+            log::warn!(
+                "alpha mode {:?} not supported by surface, using Auto",
+                self.alpha_mode
+            );
+            wgpu::CompositeAlphaMode::Auto
+        };
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: window_size.width,
+            height: window_size.height,
+            present_mode,
+            alpha_mode,
+            view_formats: vec![],
+        };
+
+        // Configuration errors are fatal in wgpu, an error scope can't catch them
+        surface.configure(&device, &surface_config);
+
+        let target = RenderTarget::Surface {
+            surface,
+            capabilities: surface_capabilities,
+            config: surface_config,
+        };
+
+        WgpuContext::from_parts(device, adapter, queue, target, window_size)
+    }
+
+    /// Creates a context without a window, rendering into an owned offscreen
+    /// color target of the given size.
+    ///
+    /// Falls back to a software adapter if no hardware adapter is available.
+    pub async fn build_headless(self, width: u32, height: u32) -> Result<WgpuContext, WgpuError> {
+        let size = winit::dpi::PhysicalSize::new(width.max(1), height.max(1));
+
+        let instance = self.create_instance(wgpu::Backends::all());
+
+        let mut options = wgpu::RequestAdapterOptions {
+            power_preference: self.power_preference,
+            compatible_surface: None,
+            force_fallback_adapter: self.force_fallback_adapter,
+        };
+
+        let adapter = match instance.request_adapter(&options).await {
+            Some(adapter) => adapter,
+            None if !options.force_fallback_adapter => {
+                log::warn!("no hardware adapter found, trying fallback adapter");
+                options.force_fallback_adapter = true;
+                instance
+                    .request_adapter(&options)
+                    .await
+                    .ok_or(WgpuError::NoFittingAdapterFound)?
+            }
+            None => return Err(WgpuError::NoFittingAdapterFound),
+        };
+
+        let (device, queue) = self.request_device(&adapter).await?;
+
+        let texture = Texture::create_render_target(
+            &device,
+            size.width,
+            size.height,
+            WgpuContext::OFFSCREEN_FORMAT,
+            Some("offscreen color target"),
+        )?;
+
+        WgpuContext::from_parts(
+            device,
+            adapter,
+            queue,
+            RenderTarget::Offscreen { texture },
+            size,
+        )
+    }
+
+    fn create_instance(&self, default_backends: wgpu::Backends) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backends.unwrap_or(default_backends),
+            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+        })
+    }
+
+    async fn request_device(
+        &self,
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), WgpuError> {
+        let adapter_features = adapter.features();
+
+        let missing = self.features - adapter_features;
+        if !missing.is_empty() {
+            return Err(WgpuError::UnsupportedFeatures(missing));
+        }
+
+        let mut failed_limit = None;
+        self.limits.check_limits_with_fail_fn(
+            &adapter.limits(),
+            true,
+            |name, requested, allowed| {
+                failed_limit = Some(WgpuError::UnsupportedLimit {
+                    name,
+                    requested,
+                    allowed,
+                })
+            },
+        );
+        if let Some(err) = failed_limit {
+            return Err(err);
+        }
+
+        let features = self.features | (self.optional_features & adapter_features);
+        let skipped = self.optional_features - adapter_features;
+        if !skipped.is_empty() {
+            log::info!("optional features not supported by adapter: {skipped:?}");
+        }
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("primary render device"),
+                    features,
+                    limits: self.limits.clone(),
+                },
+                None,
+            )
+            .await?;
+
+        match self.on_uncaptured_error.clone() {
+            Some(callback) => device.on_uncaptured_error(Box::new(move |err| callback(err))),
+            None => device.on_uncaptured_error(Box::new(|err| {
+                log::error!("uncaptured wgpu error: {err}");
+            })),
+        }
+
+        Ok((device, queue))
+    }
+
+    fn is_present_mode_auto(mode: wgpu::PresentMode) -> bool {
+        matches!(
+            mode,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+        )
+    }
+}
@@ -0,0 +1,308 @@
+use crate::wgpu::SourceMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A shader parse or validation error with everything needed to show it
+///
+/// Displays as a single line for logs, [`ShaderDiagnostic::render`] gives the
+/// full report with the offending source lines.
+#[derive(Debug, Clone)]
+pub struct ShaderDiagnostic {
+    pub label: Option<String>,
+    pub message: String,
+    /// File the spans point into, if the source came from a file
+    pub path: Option<PathBuf>,
+    pub location: Option<naga::SourceLocation>,
+    /// Highlighted spans with explanations, the first one is the primary span
+    pub labels: Vec<(naga::Span, String)>,
+    source: Option<Arc<str>>,
+}
+
+impl ShaderDiagnostic {
+    pub(crate) fn new(
+        label: Option<&str>,
+        message: impl Into<String>,
+        source: Option<&str>,
+        labels: Vec<(naga::Span, String)>,
+    ) -> Self {
+        let source = source.map(Arc::from);
+        let labels = match &source {
+            Some(source) => labels
+                .into_iter()
+                .filter(|(span, _)| in_bounds(*span, source))
+                .collect(),
+            None => Vec::new(),
+        };
+        let location = match (&source, labels.first()) {
+            (Some(source), Some((span, _))) => Some(span.location(source)),
+            _ => None,
+        };
+
+        Self {
+            label: label.map(str::to_string),
+            message: message.into(),
+            path: None,
+            location,
+            labels,
+            source,
+        }
+    }
+
+    /// Primary span in [`ShaderDiagnostic::source`]
+    pub fn span(&self) -> Option<naga::Span> {
+        self.labels.first().map(|(span, _)| *span)
+    }
+
+    /// Source the spans point into
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Points the diagnostic at the original file of preprocessed code
+    ///
+    /// Labels that end up in a different file than the primary span are dropped.
+    pub(crate) fn remap(&mut self, source_map: &SourceMap) {
+        let primary = self.span().and_then(|span| source_map.locate(span));
+        let Some((file, span, location)) = primary else {
+            // Locations in the preprocessed code mean nothing to the reader
+            self.location = None;
+            self.labels.clear();
+            self.source = None;
+            return;
+        };
+
+        let mut labels = vec![(span, std::mem::take(&mut self.labels[0].1))];
+        for (span, text) in self.labels.drain(1..) {
+            if let Some((other, span, _)) = source_map.locate(span) {
+                if std::ptr::eq(file, other) {
+                    labels.push((span, text));
+                }
+            }
+        }
+
+        self.path = file.path.clone();
+        self.location = Some(location);
+        self.labels = labels;
+        self.source = Some(Arc::from(file.source.as_str()));
+    }
+
+    /// Renders the diagnostic with the highlighted source lines, e.g.
+    ///
+    /// ```text
+    /// error: no definition in scope for identifier: 'colour'
+    ///    ┌─ shaders/lit.wgsl:12:12
+    ///    │
+    /// 12 │     return colour;
+    ///    │            ^^^^^^ unknown identifier
+    /// ```
+    pub fn render(&self) -> String {
+        let mut out = format!("error: {}\n", self.message);
+
+        let name = match (&self.path, &self.label) {
+            (Some(path), _) => path.display().to_string(),
+            (None, Some(label)) => label.clone(),
+            (None, None) => String::from("<shader>"),
+        };
+
+        let (Some(source), Some(location)) = (&self.source, self.location) else {
+            let _ = writeln!(out, "  ┌─ {name}");
+            return out;
+        };
+
+        let mut labels = self
+            .labels
+            .iter()
+            .map(|(span, text)| (span.location(source), text))
+            .collect::<Vec<_>>();
+        labels.sort_by_key(|(location, _)| location.offset);
+
+        let width = labels
+            .iter()
+            .map(|(location, _)| location.line_number.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(width);
+
+        let _ = writeln!(
+            out,
+            "{pad} ┌─ {name}:{}:{}",
+            location.line_number, location.line_position
+        );
+        let _ = writeln!(out, "{pad} │");
+
+        for (location, text) in labels {
+            let line = source
+                .lines()
+                .nth(location.line_number as usize - 1)
+                .unwrap_or_default();
+            let column = location.line_position as usize - 1;
+
+            // Spans over several lines are underlined up to the end of the first one
+            let start = line
+                .char_indices()
+                .nth(column)
+                .map_or(line.len(), |(i, _)| i);
+            let end = (start + location.length as usize).min(line.len());
+            let underline = line
+                .get(start..end)
+                .map_or(1, |spanned| spanned.chars().count().max(1));
+
+            let _ = writeln!(out, "{:>width$} │ {line}", location.line_number);
+            let _ = writeln!(
+                out,
+                "{pad} │ {}{} {text}",
+                " ".repeat(column),
+                "^".repeat(underline)
+            );
+        }
+
+        // Labels without text leave trailing spaces behind
+        let mut out = out
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+        out.push('\n');
+        out
+    }
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "`{label}`")?,
+            None => write!(f, "<unlabeled>")?,
+        }
+
+        match (&self.path, &self.location) {
+            (Some(path), Some(loc)) => write!(
+                f,
+                " at {}:{}:{}",
+                path.display(),
+                loc.line_number,
+                loc.line_position
+            )?,
+            (None, Some(loc)) => write!(f, " at {}:{}", loc.line_number, loc.line_position)?,
+            (_, None) => {}
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+fn in_bounds(span: naga::Span, source: &str) -> bool {
+    span.to_range().is_some_and(|range| {
+        range.end <= source.len()
+            && source.is_char_boundary(range.start)
+            && source.is_char_boundary(range.end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgpu::{Preprocessor, Shader, ShaderError};
+
+    /// Diagnostic with one label per `(needle, text)`, spanning the first occurrence of `needle`
+    fn diagnostic(source: &str, labels: &[(&str, &str)]) -> ShaderDiagnostic {
+        let labels = labels
+            .iter()
+            .map(|(needle, text)| {
+                let start = source.find(needle).unwrap();
+                let span = naga::Span::new(start as u32, (start + needle.len()) as u32);
+                (span, text.to_string())
+            })
+            .collect();
+        ShaderDiagnostic::new(Some("lit.wgsl"), "something is wrong", Some(source), labels)
+    }
+
+    #[test]
+    fn render_points_at_line_and_column() {
+        let source = "fn main() -> f32 {\n    return colour;\n}\n";
+        let diagnostic = diagnostic(source, &[("colour", "unknown identifier")]);
+
+        assert_eq!(
+            diagnostic.render(),
+            "error: something is wrong\n  \
+               ┌─ lit.wgsl:2:12\n  \
+               │\n\
+             2 │     return colour;\n  \
+               │            ^^^^^^ unknown identifier\n"
+        );
+    }
+
+    #[test]
+    fn render_pads_line_numbers_and_underlines_characters() {
+        let source = "\n".repeat(9) + "let a = \"äöü\";\n\nlet b = a +\n    1;\n";
+        let diagnostic = diagnostic(
+            &source,
+            &[("a +\n", "spans two lines"), ("\"äöü\"", ""), ("b", "")],
+        );
+
+        // Sorted by position, multi-byte characters take one column, spans over
+        // several lines are cut at the end of the first one
+        assert_eq!(
+            diagnostic.render(),
+            "error: something is wrong\n   \
+                ┌─ lit.wgsl:12:9\n   \
+                │\n\
+             10 │ let a = \"äöü\";\n   \
+                │         ^^^^^\n\
+             12 │ let b = a +\n   \
+                │     ^\n\
+             12 │ let b = a +\n   \
+                │         ^^^ spans two lines\n"
+        );
+    }
+
+    #[test]
+    fn render_without_source_names_the_shader() {
+        let mut diagnostic = ShaderDiagnostic::new(Some("lit"), "broken", None, Vec::new());
+        assert_eq!(diagnostic.render(), "error: broken\n  ┌─ lit\n");
+
+        diagnostic.path = Some(PathBuf::from("shaders/lit.wgsl"));
+        assert_eq!(
+            diagnostic.render(),
+            "error: broken\n  ┌─ shaders/lit.wgsl\n"
+        );
+    }
+
+    #[test]
+    fn render_points_into_included_files() {
+        let root = std::env::temp_dir().join(format!(
+            "renderer-diagnostic-include-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("common")).unwrap();
+        std::fs::write(
+            root.join("main.wgsl"),
+            "#include \"common/light.wgsl\"\n\n@fragment\nfn fs_main() -> @location(0) vec4<f32> {\n    return vec4<f32>(light());\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("common/light.wgsl"),
+            "// lights\n\nfn light() -> f32 {\n    return colour;\n}\n",
+        )
+        .unwrap();
+
+        let diagnostic = match Shader::validate_file(&Preprocessor::new(&root), "main.wgsl") {
+            Err(ShaderError::Parse(diagnostic)) => diagnostic,
+            Err(err) => panic!("expected a parse error, got {err}"),
+            Ok(_) => panic!("expected a parse error, the shader is valid"),
+        };
+
+        assert_eq!(
+            diagnostic.render(),
+            format!(
+                "error: no definition in scope for identifier: 'colour'\n  \
+                   ┌─ {}:4:12\n  \
+                   │\n\
+                 4 │     return colour;\n  \
+                   │            ^^^^^^ unknown identifier\n",
+                root.join("common/light.wgsl").display()
+            )
+        );
+    }
+}
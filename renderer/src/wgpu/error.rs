@@ -1,11 +1,296 @@
+use crate::wgpu::{ShaderDiagnostic, VertexInputType};
+use std::path::PathBuf;
+
 #[derive(Debug, thiserror::Error)]
 pub enum WgpuError {
     #[error("no suitable wgpu adapter found")]
     NoFittingAdapterFound,
 
+    #[error("adapter does not support required features {0:?}")]
+    UnsupportedFeatures(wgpu::Features),
+
+    #[error("adapter does not support limit {name}: requested {requested}, allowed {allowed}")]
+    UnsupportedLimit {
+        name: &'static str,
+        requested: u64,
+        allowed: u64,
+    },
+
     #[error(transparent)]
     RequestDeviceError(#[from] wgpu::RequestDeviceError),
 
     #[error(transparent)]
     CreateSurfaceError(#[from] wgpu::CreateSurfaceError),
+
+    #[error("failed to acquire surface texture: {0}")]
+    Surface(#[from] wgpu::SurfaceError),
+
+    #[error("context has no offscreen target")]
+    NoOffscreenTarget,
+
+    #[error("validation error for {}: {description}", display_label(label))]
+    Validation {
+        label: Option<String>,
+        description: String,
+    },
+
+    #[error("out of memory while creating {}", display_label(label))]
+    OutOfMemory { label: Option<String> },
+
+    #[error(transparent)]
+    Texture(#[from] TextureError),
+
+    #[error(transparent)]
+    Shader(#[from] ShaderError),
+
+    #[error(transparent)]
+    Buffer(#[from] BufferError),
+
+    #[error(transparent)]
+    Pipeline(#[from] PipelineError),
+
+    #[cfg(feature = "hot-reload")]
+    #[error("failed to watch shader files: {0}")]
+    Watch(#[from] notify::Error),
+}
+
+impl WgpuError {
+    /// The diagnostic of a shader parse or validation error, for showing it in full
+    pub fn shader_diagnostic(&self) -> Option<&ShaderDiagnostic> {
+        match self {
+            Self::Shader(err) => err.diagnostic(),
+            _ => None,
+        }
+    }
+
+    /// Converts an error reported by the device for the resource with the given label
+    pub(crate) fn from_device_error(err: wgpu::Error, label: Option<&str>) -> Self {
+        let label = label.map(str::to_string);
+        match err {
+            wgpu::Error::Validation { description, .. } => Self::Validation { label, description },
+            wgpu::Error::OutOfMemory { .. } => Self::OutOfMemory { label },
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TextureError {
+    #[error("failed to read texture file {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error(
+        "failed to decode image for texture {}{}: {source}",
+        display_label(label),
+        display_path(path)
+    )]
+    Decode {
+        label: Option<String>,
+        path: Option<PathBuf>,
+        source: image::ImageError,
+    },
+
+    #[error("texture {} has unsupported format {format:?}", display_label(label))]
+    UnsupportedFormat {
+        label: Option<String>,
+        format: wgpu::TextureFormat,
+    },
+
+    #[error("texture {} is missing usage {usage:?}", display_label(label))]
+    MissingUsage {
+        label: Option<String>,
+        usage: wgpu::TextureUsages,
+    },
+
+    #[error(
+        "texture {} can't be copied to in {extent:?} at {origin:?} of mip level {mip_level}",
+        display_label(label)
+    )]
+    InvalidRegion {
+        label: Option<String>,
+        mip_level: u32,
+        origin: wgpu::Origin3d,
+        extent: wgpu::Extent3d,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShaderError {
+    #[error("failed to read shader file {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error(
+        "can't tell the language of shader {} by its extension, expected .wgsl, .vert, .frag, .comp or .spv",
+        path.display()
+    )]
+    UnknownLanguage { path: PathBuf },
+
+    #[error(
+        "failed to preprocess shader{} at line {line}: {message}",
+        display_path(path)
+    )]
+    Preprocess {
+        path: Option<PathBuf>,
+        line: u32,
+        message: String,
+    },
+
+    #[error("failed to parse shader {0}")]
+    Parse(Box<ShaderDiagnostic>),
+
+    #[error("failed to validate shader {0}")]
+    Validation(Box<ShaderDiagnostic>),
+
+    #[error("shader {} has no entry point `{name}`", display_label(label))]
+    MissingEntryPoint { label: Option<String>, name: String },
+
+    #[error(
+        "entry point `{name}` of shader {} is a {found:?} entry point, expected {expected:?}",
+        display_label(label)
+    )]
+    WrongStage {
+        label: Option<String>,
+        name: String,
+        expected: naga::ShaderStage,
+        found: naga::ShaderStage,
+    },
+}
+
+impl ShaderError {
+    /// The diagnostic of a parse or validation error, for showing it in full
+    pub fn diagnostic(&self) -> Option<&ShaderDiagnostic> {
+        match self {
+            Self::Parse(diagnostic) | Self::Validation(diagnostic) => Some(diagnostic),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BufferError {
+    #[error("failed to map buffer {}: {source}", display_label(label))]
+    Map {
+        label: Option<String>,
+        source: wgpu::BufferAsyncError,
+    },
+
+    #[error("buffer {} lacks usage {usage:?}", display_label(label))]
+    MissingUsage {
+        label: Option<String>,
+        usage: wgpu::BufferUsages,
+    },
+
+    #[error(
+        "{size} bytes at offset {offset} exceed buffer {} of {buffer_size} bytes",
+        display_label(label)
+    )]
+    OutOfBounds {
+        label: Option<String>,
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+        buffer_size: wgpu::BufferAddress,
+    },
+
+    #[error(
+        "{size} bytes at offset {offset} of buffer {} are not aligned to {}",
+        display_label(label),
+        wgpu::COPY_BUFFER_ALIGNMENT
+    )]
+    Unaligned {
+        label: Option<String>,
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+    },
+
+    #[error(
+        "buffer {} holds {expected} elements, got {found}",
+        display_label(label)
+    )]
+    LengthMismatch {
+        label: Option<String>,
+        expected: u64,
+        found: u64,
+    },
+
+    #[error(
+        "buffer {} can't hold {len} elements of {element_size} bytes, the size overflows",
+        display_label(label)
+    )]
+    TooLarge {
+        label: Option<String>,
+        len: u64,
+        element_size: u64,
+    },
+
+    #[error(
+        "buffer {} has no room for {requested} elements, the largest free range holds {largest_free}",
+        display_label(label)
+    )]
+    OutOfSpace {
+        label: Option<String>,
+        requested: u64,
+        largest_free: u64,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    #[error(
+        "vertex input mismatch in pipeline {} at location {location}: shader expects {expected}, buffer provides {provided:?}",
+        display_label(label)
+    )]
+    VertexInputMismatch {
+        label: Option<String>,
+        location: u32,
+        expected: VertexInputType,
+        provided: wgpu::VertexFormat,
+    },
+
+    #[error(
+        "vertex input missing in pipeline {} at location {location}: shader expects {expected}, no buffer provides it",
+        display_label(label)
+    )]
+    MissingVertexInput {
+        label: Option<String>,
+        location: u32,
+        expected: VertexInputType,
+    },
+
+    #[error(
+        "shaders declare @group({group}) @binding({binding}) as both {first:?} and {second:?}"
+    )]
+    ConflictingBinding {
+        group: u32,
+        binding: u32,
+        first: wgpu::BindingType,
+        second: wgpu::BindingType,
+    },
+
+    #[error(
+        "compute pipeline {} has workgroup size {size:?}, every component has to be at least 1",
+        display_label(label)
+    )]
+    InvalidWorkgroupSize {
+        label: Option<String>,
+        size: [u32; 3],
+    },
+}
+
+fn display_label(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("`{label}`"),
+        None => String::from("<unlabeled>"),
+    }
+}
+
+fn display_path(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => format!(" ({})", path.display()),
+        None => String::new(),
+    }
 }
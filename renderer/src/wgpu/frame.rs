@@ -0,0 +1,162 @@
+use crate::wgpu::{Texture, WgpuContext, WgpuError};
+
+/// A frame in flight, acquired with [`WgpuContext::begin_frame`] or
+/// [`WgpuContext::begin_capture_frame`]
+///
+/// Holds the color target view and a command encoder. Nothing is submitted
+/// or presented until [`Frame::finish`] is called, dropping the frame
+/// discards everything recorded into it.
+pub struct Frame<'a> {
+    ctx: &'a WgpuContext,
+    surface_texture: Option<wgpu::SurfaceTexture>,
+    /// Texture rendered into instead of the surface by capture frames
+    capture_target: Option<Texture>,
+    view: wgpu::TextureView,
+    encoder: wgpu::CommandEncoder,
+}
+
+impl<'a> Frame<'a> {
+    pub(super) fn new(ctx: &'a WgpuContext, surface_texture: Option<wgpu::SurfaceTexture>) -> Self {
+        let view = match &surface_texture {
+            Some(surface_texture) => surface_texture
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            // Can't crash, frames without a surface texture are only created for headless contexts
+            None => ctx
+                .offscreen_target()
+                .unwrap_or_else(|| unreachable!())
+                .raw
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        };
+
+        Self::with_view(ctx, surface_texture, None, view)
+    }
+
+    pub(super) fn new_capture(ctx: &'a WgpuContext, capture_target: Texture) -> Self {
+        let view = capture_target
+            .raw
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self::with_view(ctx, None, Some(capture_target), view)
+    }
+
+    fn with_view(
+        ctx: &'a WgpuContext,
+        surface_texture: Option<wgpu::SurfaceTexture>,
+        capture_target: Option<Texture>,
+        view: wgpu::TextureView,
+    ) -> Self {
+        let encoder = ctx
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("frame command encoder"),
+            });
+
+        Self {
+            ctx,
+            surface_texture,
+            capture_target,
+            view,
+            encoder,
+        }
+    }
+
+    /// View of the color target, the surface texture, offscreen target or capture target
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// View of the context's depth buffer
+    pub fn depth_view(&self) -> &'a wgpu::TextureView {
+        &self.ctx.depth_buffer().view
+    }
+
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
+        &mut self.encoder
+    }
+
+    /// Color target view and encoder at the same time, for recording custom passes
+    pub fn view_and_encoder(&mut self) -> (&wgpu::TextureView, &mut wgpu::CommandEncoder) {
+        (&self.view, &mut self.encoder)
+    }
+
+    /// Begins a render pass on the color target and depth buffer
+    ///
+    /// Both get cleared if `clear_color` is set, otherwise their contents are kept.
+    pub fn begin_render_pass(
+        &mut self,
+        label: Option<&str>,
+        clear_color: Option<wgpu::Color>,
+    ) -> wgpu::RenderPass<'_> {
+        let depth_ops = wgpu::Operations {
+            load: match clear_color {
+                Some(_) => wgpu::LoadOp::Clear(1.0),
+                None => wgpu::LoadOp::Load,
+            },
+            store: true,
+        };
+
+        self.begin_render_pass_with(label, clear_color, Some(depth_ops))
+    }
+
+    /// Begins a render pass on the color target, with the depth buffer only if
+    /// `depth_ops` is set
+    ///
+    /// Passes without depth are for pipelines built without depth/stencil
+    /// state, see [`RenderPipelineBuilder::depth_stencil`](crate::wgpu::RenderPipelineBuilder::depth_stencil).
+    pub fn begin_render_pass_with(
+        &mut self,
+        label: Option<&str>,
+        clear_color: Option<wgpu::Color>,
+        depth_ops: Option<wgpu::Operations<f32>>,
+    ) -> wgpu::RenderPass<'_> {
+        let depth_view = self.depth_view();
+
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: clear_color.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: depth_ops.map(|depth_ops| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(depth_ops),
+                    stencil_ops: None,
+                }
+            }),
+        })
+    }
+
+    /// Submits the recorded commands and presents the frame
+    pub fn finish(self) {
+        self.ctx.queue().submit(Some(self.encoder.finish()));
+
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+
+    /// Submits the recorded commands and copies the color target into an image
+    ///
+    /// Only works for frames of headless contexts and capture frames, see
+    /// [`WgpuContext::begin_capture_frame`]. Surface frames are presented and
+    /// fail with [`WgpuError::NoOffscreenTarget`].
+    pub fn finish_and_capture(self) -> Result<image::RgbaImage, WgpuError> {
+        self.ctx.queue().submit(Some(self.encoder.finish()));
+
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+            return Err(WgpuError::NoOffscreenTarget);
+        }
+
+        match &self.capture_target {
+            Some(texture) => texture.read_to_image(self.ctx.device(), self.ctx.queue()),
+            None => self.ctx.read_offscreen_target(),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::wgpu::{Buffer, BufferError, WgpuContext, WgpuError};
+
+/// A growable array of `T` mirrored in a GPU buffer
+///
+/// Changes are made on the CPU side and uploaded by [`GpuVec::sync`], which
+/// only writes the elements added since the last sync. Once the data outgrows
+/// the buffer, a new one of twice the capacity is created and the uploaded
+/// contents are copied over on the GPU. That replaces [`GpuVec::raw`], so
+/// bind groups using it have to be rebuilt, `sync` says when that happened.
+///
+/// The buffer always has [`wgpu::BufferUsages::COPY_DST`] and
+/// [`wgpu::BufferUsages::COPY_SRC`] on top of the requested usages.
+pub struct GpuVec<T: bytemuck::Pod> {
+    buffer: Buffer,
+    label: Option<String>,
+    usages: wgpu::BufferUsages,
+    data: Vec<T>,
+    capacity: usize,
+    /// Leading elements whose GPU copy is up to date
+    uploaded: usize,
+}
+
+impl<T: bytemuck::Pod> GpuVec<T> {
+    const ELEMENT_SIZE: wgpu::BufferAddress = std::mem::size_of::<T>() as wgpu::BufferAddress;
+
+    pub fn new(
+        ctx: &WgpuContext,
+        usages: wgpu::BufferUsages,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        Self::with_capacity(ctx, 1, usages, label)
+    }
+
+    /// Creates the buffer with room for `capacity` elements, at least one
+    pub fn with_capacity(
+        ctx: &WgpuContext,
+        capacity: usize,
+        usages: wgpu::BufferUsages,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let capacity = capacity.max(1);
+        let usages = usages | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        let buffer = Self::allocate(ctx, capacity, usages, label)?;
+
+        Ok(Self {
+            buffer,
+            label: label.map(str::to_string),
+            usages,
+            data: Vec::with_capacity(capacity),
+            capacity,
+            uploaded: 0,
+        })
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.data.push(value);
+    }
+
+    pub fn extend(&mut self, values: impl IntoIterator<Item = T>) {
+        self.data.extend(values);
+    }
+
+    /// Removes all elements, the buffer keeps its capacity
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.uploaded = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Number of elements the current buffer has room for
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// CPU side contents, including elements that aren't synced yet
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Current GPU buffer, replaced when [`GpuVec::sync`] grows it
+    pub fn raw(&self) -> &wgpu::Buffer {
+        self.buffer.raw()
+    }
+
+    /// Uploads the elements added since the last sync, growing the buffer if needed
+    ///
+    /// Returns `true` if the buffer was replaced, so everything referring to
+    /// the old [`GpuVec::raw`] has to be recreated.
+    pub fn sync(&mut self, ctx: &WgpuContext) -> Result<bool, WgpuError> {
+        let grown = self.data.len() > self.capacity;
+        if grown {
+            let capacity = self.data.len().max(self.capacity.saturating_mul(2));
+            let buffer = Self::allocate(ctx, capacity, self.usages, self.label.as_deref())?;
+
+            // Only whole words can be copied, a partial one is uploaded again below
+            let copy_size = align_down(self.uploaded as u64 * Self::ELEMENT_SIZE);
+            if copy_size > 0 {
+                let mut encoder =
+                    ctx.device()
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("gpu vec grow encoder"),
+                        });
+                encoder.copy_buffer_to_buffer(self.buffer.raw(), 0, buffer.raw(), 0, copy_size);
+                ctx.queue().submit(Some(encoder.finish()));
+            }
+
+            self.buffer = buffer;
+            self.capacity = capacity;
+        }
+
+        if self.uploaded < self.data.len() {
+            let bytes: &[u8] = bytemuck::cast_slice(&self.data);
+            let start = align_down(self.uploaded as u64 * Self::ELEMENT_SIZE) as usize;
+
+            // Writes have to cover whole words, the buffer size is rounded up accordingly
+            let mut pending = bytes[start..].to_vec();
+            pending.resize(align_up(pending.len() as u64) as usize, 0);
+            self.buffer.write(ctx.queue(), start as u64, &pending)?;
+
+            self.uploaded = self.data.len();
+        }
+
+        Ok(grown)
+    }
+
+    fn allocate(
+        ctx: &WgpuContext,
+        capacity: usize,
+        usages: wgpu::BufferUsages,
+        label: Option<&str>,
+    ) -> Result<Buffer, WgpuError> {
+        // Rounded up to whole words, like writes
+        let size = (capacity as u64)
+            .checked_mul(Self::ELEMENT_SIZE)
+            .and_then(|size| size.checked_next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT))
+            .ok_or_else(|| BufferError::TooLarge {
+                label: label.map(str::to_string),
+                len: capacity as u64,
+                element_size: Self::ELEMENT_SIZE,
+            })?;
+        Buffer::new(ctx.device(), size, usages, false, label)
+    }
+}
+
+fn align_down(size: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    size - size % wgpu::COPY_BUFFER_ALIGNMENT
+}
+
+fn align_up(size: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    size.div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT
+}
@@ -0,0 +1,148 @@
+use crate::wgpu::{
+    Preprocessor, RenderPipeline, RenderPipelineBuilder, ShaderSource, WgpuContext, WgpuError,
+};
+use notify::Watcher;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Watches a shader directory and collects the files that changed
+pub struct ShaderWatcher {
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderWatcher {
+    /// Watches everything below `root`, including subdirectories
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, WgpuError> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // Only fails once the watcher itself is being dropped
+            let _ = sender.send(event);
+        })?;
+        watcher.watch(root.as_ref(), notify::RecursiveMode::Recursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Canonical paths of all files that changed since the last call, never blocks
+    pub fn changed_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if !event.kind.is_access() => {
+                    paths.extend(event.paths.iter().map(|path| canonical(path)));
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("error while watching shaders: {e}"),
+            }
+        }
+
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// A [`RenderPipeline`] built from a shader file that can be rebuilt at runtime
+///
+/// If rebuilding fails, the last pipeline that was built successfully stays in use.
+pub struct ReloadablePipeline<'a> {
+    builder: RenderPipelineBuilder<'a>,
+    preprocessor: Preprocessor,
+    path: PathBuf,
+    pipeline: RenderPipeline,
+    /// Canonical paths of the shader and everything it includes
+    dependencies: Vec<PathBuf>,
+}
+
+impl<'a> ReloadablePipeline<'a> {
+    /// Builds the pipeline from the shader at `path`, relative to the preprocessor's root
+    pub fn new(
+        ctx: &WgpuContext,
+        preprocessor: Preprocessor,
+        path: impl Into<PathBuf>,
+        builder: RenderPipelineBuilder<'a>,
+    ) -> Result<Self, WgpuError> {
+        let path = path.into();
+        let (pipeline, dependencies) = Self::build(ctx, &preprocessor, &path, &builder)?;
+
+        Ok(Self {
+            builder,
+            preprocessor,
+            path,
+            pipeline,
+            dependencies,
+        })
+    }
+
+    pub fn pipeline(&self) -> &RenderPipeline {
+        &self.pipeline
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the shader or one of its includes is at `path`
+    pub fn depends_on(&self, path: impl AsRef<Path>) -> bool {
+        self.dependencies.contains(&canonical(path.as_ref()))
+    }
+
+    /// Rebuilds the pipeline from disk, keeping the current one on failure
+    pub fn reload(&mut self, ctx: &WgpuContext) -> Result<(), WgpuError> {
+        let (pipeline, dependencies) =
+            Self::build(ctx, &self.preprocessor, &self.path, &self.builder)?;
+        self.pipeline = pipeline;
+        self.dependencies = dependencies;
+
+        Ok(())
+    }
+
+    /// Reloads if one of `changed` is a dependency
+    ///
+    /// Returns `true` if a new pipeline is in use. On failure the last good
+    /// pipeline stays in use and the error is returned, e.g. to show its
+    /// [`WgpuError::shader_diagnostic`].
+    pub fn reload_if_changed(
+        &mut self,
+        ctx: &WgpuContext,
+        changed: &[PathBuf],
+    ) -> Result<bool, WgpuError> {
+        if !changed.iter().any(|path| self.depends_on(path)) {
+            return Ok(false);
+        }
+
+        self.reload(ctx)?;
+        Ok(true)
+    }
+
+    fn build(
+        ctx: &WgpuContext,
+        preprocessor: &Preprocessor,
+        path: &Path,
+        builder: &RenderPipelineBuilder<'a>,
+    ) -> Result<(RenderPipeline, Vec<PathBuf>), WgpuError> {
+        let source = preprocessor.process_file(path)?;
+        let dependencies = source
+            .source_map()
+            .files()
+            .iter()
+            .filter_map(|file| file.path.as_deref())
+            .map(canonical)
+            .collect();
+
+        let pipeline = builder.build(ctx, ShaderSource::Preprocessed(&source))?;
+
+        Ok((pipeline, dependencies))
+    }
+}
+
+/// Editors often replace files instead of writing them, so paths can vanish for a moment
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
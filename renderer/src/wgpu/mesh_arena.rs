@@ -0,0 +1,598 @@
+use crate::wgpu::{Buffer, BufferError, WgpuContext, WgpuError};
+use std::marker::PhantomData;
+use std::ops::Range;
+
+/// Meshes sub-allocated from one shared vertex and one shared `u32` index buffer
+///
+/// Space is handed out first-fit from a free list of element ranges, freed
+/// ranges are merged with their neighbours. Allocations start and end on
+/// multiples of the arena's alignment, at least whole words, so meshes may be
+/// followed by padding elements. Meshes are drawn with their index range and a base vertex, which
+/// needs [`wgpu::DownlevelFlags::BASE_VERTEX`] on downlevel backends.
+///
+/// Meshes are referred to by [`MeshHandle`]s since [`MeshArena::defragment`]
+/// moves them, the current place is looked up with [`MeshArena::get`].
+pub struct MeshArena<V: bytemuck::Pod> {
+    vertices: Space,
+    indices: Space,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    _marker: PhantomData<V>,
+}
+
+/// Refers to a mesh in a [`MeshArena`], stays valid until the mesh is removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshHandle {
+    index: u32,
+    generation: u32,
+}
+
+/// Where a mesh lives in the buffers of a [`MeshArena`], in elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshAllocation {
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    pub index_offset: u32,
+    pub index_count: u32,
+}
+
+/// Occupancy of a [`MeshArena`], see [`MeshArena::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    pub meshes: usize,
+    pub vertices: SpaceStats,
+    pub indices: SpaceStats,
+}
+
+/// Occupancy of one buffer of a [`MeshArena`], in elements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpaceStats {
+    pub capacity: u64,
+    /// Allocated elements, including alignment padding
+    pub used: u64,
+    pub largest_free: u64,
+    /// Number of separate free ranges
+    pub free_ranges: usize,
+}
+
+struct Slot {
+    generation: u32,
+    mesh: Option<MeshAllocation>,
+}
+
+/// A buffer and the free list of its elements
+struct Space {
+    buffer: Buffer,
+    element_size: wgpu::BufferAddress,
+    /// Elements per allocation step, keeping offsets and sizes aligned
+    alignment: u64,
+    free_list: FreeList,
+}
+
+/// Free ranges of `0..capacity`
+struct FreeList {
+    capacity: u64,
+    /// Sorted and never adjacent, neighbours are merged
+    ranges: Vec<Range<u64>>,
+}
+
+impl<V: bytemuck::Pod> MeshArena<V> {
+    /// Creates the shared buffers with room for the given number of vertices and indices
+    ///
+    /// Every allocation starts at a multiple of `alignment` bytes in its
+    /// buffer, rounded up to [`wgpu::COPY_BUFFER_ALIGNMENT`]. Larger alignments
+    /// allow binding meshes as storage buffers too, e.g. with
+    /// [`wgpu::Limits::min_storage_buffer_offset_alignment`].
+    pub fn new(
+        device: &wgpu::Device,
+        vertex_capacity: u32,
+        index_capacity: u32,
+        alignment: wgpu::BufferAddress,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let vertex_label = label.map(|label| format!("{label} vertices"));
+        let vertices = Space::new(
+            device,
+            vertex_capacity.into(),
+            std::mem::size_of::<V>() as wgpu::BufferAddress,
+            alignment,
+            wgpu::BufferUsages::VERTEX,
+            vertex_label.as_deref(),
+        )?;
+        let index_label = label.map(|label| format!("{label} indices"));
+        let indices = Space::new(
+            device,
+            index_capacity.into(),
+            std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            alignment,
+            wgpu::BufferUsages::INDEX,
+            index_label.as_deref(),
+        )?;
+
+        Ok(Self {
+            vertices,
+            indices,
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Allocates room for a mesh and schedules uploading it
+    ///
+    /// `indices` are relative to the mesh's first vertex, meshes without
+    /// indices are drawn as a plain vertex range.
+    pub fn insert(
+        &mut self,
+        queue: &wgpu::Queue,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> Result<MeshHandle, WgpuError> {
+        let vertex_offset = self.vertices.allocate(vertices.len() as u64)?;
+        let index_offset = match self.indices.allocate(indices.len() as u64) {
+            Ok(offset) => offset,
+            Err(err) => {
+                self.vertices.free(vertex_offset, vertices.len() as u64);
+                return Err(err.into());
+            }
+        };
+
+        self.vertices
+            .write(queue, vertex_offset, bytemuck::cast_slice(vertices))?;
+        self.indices
+            .write(queue, index_offset, bytemuck::cast_slice(indices))?;
+
+        // Offsets fit a u32, the capacities do
+        let mesh = MeshAllocation {
+            vertex_offset: vertex_offset as u32,
+            vertex_count: vertices.len() as u32,
+            index_offset: index_offset as u32,
+            index_count: indices.len() as u32,
+        };
+
+        let index = match self.free_slots.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    mesh: None,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.mesh = Some(mesh);
+
+        Ok(MeshHandle {
+            index,
+            generation: slot.generation,
+        })
+    }
+
+    /// Frees the mesh's space, returns `false` if it was removed already
+    pub fn remove(&mut self, handle: MeshHandle) -> bool {
+        let Some(slot) = self
+            .slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+        else {
+            return false;
+        };
+        let Some(mesh) = slot.mesh.take() else {
+            return false;
+        };
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.index);
+        self.vertices
+            .free(mesh.vertex_offset.into(), mesh.vertex_count.into());
+        self.indices
+            .free(mesh.index_offset.into(), mesh.index_count.into());
+
+        true
+    }
+
+    /// Current place of the mesh, `None` if it was removed
+    pub fn get(&self, handle: MeshHandle) -> Option<MeshAllocation> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.mesh)
+    }
+
+    pub fn vertex_buffer(&self) -> &Buffer {
+        &self.vertices.buffer
+    }
+
+    pub fn index_buffer(&self) -> &Buffer {
+        &self.indices.buffer
+    }
+
+    /// Binds the vertex buffer at `slot` and the index buffer, for drawing
+    /// meshes with [`MeshAllocation::draw`]
+    pub fn bind<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, slot: u32) {
+        rpass.set_vertex_buffer(slot, self.vertices.buffer.raw().slice(..));
+        if self.indices.buffer.size() > 0 {
+            rpass.set_index_buffer(
+                self.indices.buffer.raw().slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+        }
+    }
+
+    /// Moves all meshes to the start of new buffers, leaving a single free range
+    ///
+    /// The contents are copied on the GPU. This replaces both buffers, and
+    /// [`MeshArena::get`] returns the new places afterwards. If creating a
+    /// buffer fails, the arena is left untouched.
+    pub fn defragment(&mut self, ctx: &WgpuContext) -> Result<(), WgpuError> {
+        let mut encoder = ctx
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("mesh arena defragment encoder"),
+            });
+
+        // New places go into copies, so a failure leaves the arena as it was
+        let mut meshes: Vec<_> = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| Some((index, slot.mesh?)))
+            .collect();
+
+        meshes.sort_by_key(|(_, mesh)| mesh.vertex_offset);
+        let vertices = self.vertices.compact(
+            ctx.device(),
+            &mut encoder,
+            meshes
+                .iter_mut()
+                .map(|(_, mesh)| (&mut mesh.vertex_offset, mesh.vertex_count)),
+        )?;
+
+        meshes.sort_by_key(|(_, mesh)| mesh.index_offset);
+        let indices = self.indices.compact(
+            ctx.device(),
+            &mut encoder,
+            meshes
+                .iter_mut()
+                .map(|(_, mesh)| (&mut mesh.index_offset, mesh.index_count)),
+        )?;
+
+        ctx.queue().submit(Some(encoder.finish()));
+        self.vertices = vertices;
+        self.indices = indices;
+        for (index, mesh) in meshes {
+            self.slots[index].mesh = Some(mesh);
+        }
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            meshes: self.slots.len() - self.free_slots.len(),
+            vertices: self.vertices.stats(),
+            indices: self.indices.stats(),
+        }
+    }
+}
+
+impl MeshAllocation {
+    pub fn vertices(&self) -> Range<u32> {
+        self.vertex_offset..self.vertex_offset + self.vertex_count
+    }
+
+    pub fn indices(&self) -> Range<u32> {
+        self.index_offset..self.index_offset + self.index_count
+    }
+
+    /// Value added to the mesh's indices to address the shared vertex buffer
+    pub fn base_vertex(&self) -> i32 {
+        self.vertex_offset as i32
+    }
+
+    /// Draws the mesh from the buffers bound by [`MeshArena::bind`]
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        match self.index_count {
+            0 => rpass.draw(self.vertices(), instances),
+            _ => rpass.draw_indexed(self.indices(), self.base_vertex(), instances),
+        }
+    }
+}
+
+impl SpaceStats {
+    pub fn free(&self) -> u64 {
+        self.capacity - self.used
+    }
+
+    /// Share of the free space outside the largest free range, from 0 to 1
+    ///
+    /// High values mean allocations may fail even though there is enough free
+    /// space in total, [`MeshArena::defragment`] brings it back to 0.
+    pub fn fragmentation(&self) -> f32 {
+        match self.free() {
+            0 => 0.0,
+            free => 1.0 - self.largest_free as f32 / free as f32,
+        }
+    }
+}
+
+impl Space {
+    /// A space whose allocations start at multiples of `alignment` bytes
+    fn new(
+        device: &wgpu::Device,
+        capacity: u64,
+        element_size: wgpu::BufferAddress,
+        alignment: wgpu::BufferAddress,
+        usages: wgpu::BufferUsages,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let usages = usages | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        let buffer = Buffer::new(device, capacity * element_size, usages, false, label)?;
+        // Fewest elements spanning a multiple of both the alignment and a word
+        let alignment = lcm(alignment.max(1), wgpu::COPY_BUFFER_ALIGNMENT);
+        let alignment = alignment / gcd(element_size, alignment);
+
+        Ok(Self {
+            buffer,
+            element_size,
+            alignment,
+            // Trailing elements can't be allocated if they don't fill a whole step
+            free_list: FreeList::new(capacity - capacity % alignment),
+        })
+    }
+
+    /// Allocates `len` elements, returns the offset
+    fn allocate(&mut self, len: u64) -> Result<u64, BufferError> {
+        if len == 0 {
+            return Ok(0);
+        }
+
+        self.free_list
+            .allocate(align_up(len, self.alignment), self.alignment)
+            .ok_or_else(|| BufferError::OutOfSpace {
+                label: self.buffer.label().map(str::to_string),
+                requested: len,
+                largest_free: self.free_list.largest(),
+            })
+    }
+
+    fn free(&mut self, offset: u64, len: u64) {
+        if len > 0 {
+            self.free_list
+                .free(offset..offset + align_up(len, self.alignment));
+        }
+    }
+
+    /// Writes elements at `offset`, zero padded to whole words
+    fn write(&self, queue: &wgpu::Queue, offset: u64, data: &[u8]) -> Result<(), WgpuError> {
+        let offset = offset * self.element_size;
+        match data.len() as wgpu::BufferAddress % wgpu::COPY_BUFFER_ALIGNMENT {
+            0 => self.buffer.write(queue, offset, data),
+            rest => {
+                let mut padded = data.to_vec();
+                padded.resize(
+                    data.len() + (wgpu::COPY_BUFFER_ALIGNMENT - rest) as usize,
+                    0,
+                );
+                self.buffer.write(queue, offset, &padded)
+            }
+        }
+    }
+
+    /// Copies the given allocations into a new space one after the other,
+    /// updating their offsets
+    fn compact<'a>(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        allocations: impl Iterator<Item = (&'a mut u32, u32)>,
+    ) -> Result<Self, WgpuError> {
+        let mut space = Self::new(
+            device,
+            self.free_list.capacity,
+            self.element_size,
+            self.alignment * self.element_size,
+            self.buffer.usages(),
+            self.buffer.label(),
+        )?;
+
+        let mut end = 0;
+        for (offset, len) in allocations {
+            let len = align_up(len.into(), self.alignment);
+            if len == 0 {
+                continue;
+            }
+
+            encoder.copy_buffer_to_buffer(
+                self.buffer.raw(),
+                u64::from(*offset) * self.element_size,
+                space.buffer.raw(),
+                end * self.element_size,
+                len * self.element_size,
+            );
+            *offset = end as u32;
+            end += len;
+        }
+
+        space.free_list = FreeList::used_up_to(space.free_list.capacity, end);
+
+        Ok(space)
+    }
+
+    fn stats(&self) -> SpaceStats {
+        let free: u64 = self
+            .free_list
+            .ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum();
+
+        SpaceStats {
+            capacity: self.free_list.capacity,
+            used: self.free_list.capacity - free,
+            largest_free: self.free_list.largest(),
+            free_ranges: self.free_list.ranges.len(),
+        }
+    }
+}
+
+impl FreeList {
+    fn new(capacity: u64) -> Self {
+        Self::used_up_to(capacity, 0)
+    }
+
+    /// A free list with everything before `end` allocated
+    fn used_up_to(capacity: u64, end: u64) -> Self {
+        let mut ranges = Vec::new();
+        if end < capacity {
+            ranges.push(end..capacity);
+        }
+
+        Self { capacity, ranges }
+    }
+
+    /// Takes `len` elements from the first range with room for them at an
+    /// `alignment` multiple
+    fn allocate(&mut self, len: u64, alignment: u64) -> Option<u64> {
+        let (index, start) = self.ranges.iter().enumerate().find_map(|(index, range)| {
+            let start = align_up(range.start, alignment);
+            (start + len <= range.end).then_some((index, start))
+        })?;
+
+        let range = self.ranges[index].clone();
+        let rest = [range.start..start, start + len..range.end];
+        self.ranges.splice(
+            index..=index,
+            rest.into_iter().filter(|rest| !rest.is_empty()),
+        );
+
+        Some(start)
+    }
+
+    fn free(&mut self, range: Range<u64>) {
+        let index = self.ranges.partition_point(|free| free.start < range.start);
+        let merges_prev = index > 0 && self.ranges[index - 1].end == range.start;
+        let merges_next = self
+            .ranges
+            .get(index)
+            .is_some_and(|next| next.start == range.end);
+
+        match (merges_prev, merges_next) {
+            (true, true) => {
+                self.ranges[index - 1].end = self.ranges[index].end;
+                self.ranges.remove(index);
+            }
+            (true, false) => self.ranges[index - 1].end = range.end,
+            (false, true) => self.ranges[index].start = range.start,
+            (false, false) => self.ranges.insert(index, range),
+        }
+    }
+
+    fn largest(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The free ranges as `(start, end)`
+    fn free_ranges(free_list: &FreeList) -> Vec<(u64, u64)> {
+        free_list
+            .ranges
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn allocate_takes_the_first_fit() {
+        let mut free_list = FreeList::new(16);
+
+        assert_eq!(free_list.allocate(4, 1), Some(0));
+        assert_eq!(free_list.allocate(6, 1), Some(4));
+        assert_eq!(free_ranges(&free_list), [(10, 16)]);
+        assert_eq!(free_list.allocate(7, 1), None);
+        assert_eq!(free_list.allocate(6, 1), Some(10));
+        assert!(free_list.ranges.is_empty());
+    }
+
+    #[test]
+    fn allocate_keeps_the_range_skipped_for_alignment() {
+        let mut free_list = FreeList::used_up_to(16, 1);
+
+        assert_eq!(free_list.allocate(4, 4), Some(4));
+        assert_eq!(free_ranges(&free_list), [(1, 4), (8, 16)]);
+        assert_eq!(free_list.allocate(3, 1), Some(1));
+        assert_eq!(free_ranges(&free_list), [(8, 16)]);
+    }
+
+    #[test]
+    fn free_merges_with_neighbours() {
+        let mut free_list = FreeList::used_up_to(16, 16);
+
+        free_list.free(4..6);
+        assert_eq!(free_ranges(&free_list), [(4, 6)]);
+        // Merges with the range before
+        free_list.free(6..8);
+        assert_eq!(free_ranges(&free_list), [(4, 8)]);
+        // Merges with the range after
+        free_list.free(2..4);
+        assert_eq!(free_ranges(&free_list), [(2, 8)]);
+        // Doesn't touch either
+        free_list.free(12..14);
+        assert_eq!(free_ranges(&free_list), [(2, 8), (12, 14)]);
+        // Joins both
+        free_list.free(8..12);
+        assert_eq!(free_ranges(&free_list), [(2, 14)]);
+        free_list.free(0..2);
+        free_list.free(14..16);
+        assert_eq!(free_ranges(&free_list), [(0, 16)]);
+    }
+
+    #[test]
+    fn freed_ranges_are_reused_after_fragmentation() {
+        let mut free_list = FreeList::new(12);
+        let allocations: Vec<_> = (0..6).map(|_| free_list.allocate(2, 1).unwrap()).collect();
+        assert_eq!(allocations, [0, 2, 4, 6, 8, 10]);
+
+        // Every other allocation is freed, leaving 6 free elements in pieces of 2
+        for &start in allocations.iter().step_by(2) {
+            free_list.free(start..start + 2);
+        }
+        assert_eq!(free_ranges(&free_list), [(0, 2), (4, 6), (8, 10)]);
+        assert_eq!(free_list.largest(), 2);
+        assert_eq!(free_list.allocate(3, 1), None);
+
+        assert_eq!(free_list.allocate(2, 1), Some(0));
+        assert_eq!(free_list.allocate(1, 1), Some(4));
+        assert_eq!(free_ranges(&free_list), [(5, 6), (8, 10)]);
+
+        // Freeing the middle allocation joins the pieces around it
+        free_list.free(6..8);
+        assert_eq!(free_ranges(&free_list), [(5, 10)]);
+        assert_eq!(free_list.allocate(5, 1), Some(5));
+        assert!(free_list.ranges.is_empty());
+    }
+}
@@ -1,15 +1,45 @@
 mod buffer;
+mod compute_pipeline;
 mod context;
+mod context_builder;
+mod diagnostic;
 mod error;
+mod frame;
+mod gpu_vec;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+mod mesh_arena;
 mod pipeline;
+mod preprocessor;
+mod reflection;
+mod scope;
 mod shader;
+mod shader_library;
+mod staging_belt;
 mod texture;
+mod typed_buffer;
 mod vertex;
 
 pub use buffer::Buffer;
+pub use compute_pipeline::{ComputePipeline, ComputePipelineBuilder};
 pub use context::WgpuContext;
-pub use error::WgpuError;
-pub use pipeline::{RenderPipeline, ShaderSource};
+pub use context_builder::WgpuContextBuilder;
+pub use diagnostic::ShaderDiagnostic;
+pub use error::{BufferError, PipelineError, ShaderError, TextureError, WgpuError};
+pub use frame::Frame;
+pub use gpu_vec::GpuVec;
+#[cfg(feature = "hot-reload")]
+pub use hot_reload::{ReloadablePipeline, ShaderWatcher};
+pub use mesh_arena::{ArenaStats, MeshAllocation, MeshArena, MeshHandle, SpaceStats};
+/// Stage of a shader entry point, used by reflection and GLSL input
+pub use naga::ShaderStage;
+pub use pipeline::{RenderPipeline, RenderPipelineBuilder, ShaderSource};
+pub use preprocessor::{PreprocessedSource, Preprocessor, SourceFile, SourceMap};
+pub use reflection::{EntryPoint, ResourceBinding, ShaderReflection, VertexInput, VertexInputType};
+pub use renderer_derive::Vertex;
 pub use shader::Shader;
+pub use shader_library::ShaderLibrary;
+pub use staging_belt::{StagingBelt, TextureWrite, UploadStats};
 pub use texture::Texture;
+pub use typed_buffer::TypedBuffer;
 pub use vertex::Vertex;
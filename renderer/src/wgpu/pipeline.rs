@@ -1,92 +1,72 @@
 use crate::wgpu::shader::Shader;
-use crate::wgpu::{Texture, WgpuContext};
-
-// TODO: Struct for configuring pipeline (with most settings having a default implemented)
+use crate::wgpu::{reflection, scope};
+use crate::wgpu::{EntryPoint, PipelineError, PreprocessedSource, Texture, WgpuContext, WgpuError};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 pub enum ShaderSource<'a> {
+    /// WGSL source code
     SourceCode(&'a str),
+    Preprocessed(&'a PreprocessedSource),
+    Glsl {
+        source: &'a str,
+        stage: naga::ShaderStage,
+    },
+    SpirV(&'a [u8]),
     Module(wgpu::ShaderModule),
     Struct(Shader),
+    /// A shader shared between pipelines, e.g. one from a [`ShaderLibrary`](crate::wgpu::ShaderLibrary)
+    Shared(Arc<Shader>),
 }
 
 pub struct RenderPipeline {
     raw: wgpu::RenderPipeline,
     layout: wgpu::PipelineLayout,
-    shader: Shader,
+    bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    shader: Arc<Shader>,
+    fragment_shader: Option<Arc<Shader>>,
 }
 
-impl RenderPipeline {
-    pub fn new<'a>(
-        ctx: &WgpuContext,
-        shader: ShaderSource,
-        buffers: &'a [wgpu::VertexBufferLayout<'a>],
+impl<'a> ShaderSource<'a> {
+    pub(crate) fn into_shader(
+        self,
+        device: &wgpu::Device,
         label: Option<&str>,
-    ) -> Self {
-        let shader_label = label.map(|lbl| format!("shader for pipeline {lbl}"));
-        let shader = match shader {
-            ShaderSource::SourceCode(src) => {
-                Shader::new(ctx.device(), src, shader_label.as_ref().map(|v| v.as_str()))
+    ) -> Result<Arc<Shader>, WgpuError> {
+        let shader = match self {
+            ShaderSource::SourceCode(src) => Shader::new(device, src, label)?,
+            ShaderSource::Preprocessed(source) => Shader::from_preprocessed(device, source, label)?,
+            ShaderSource::Glsl { source, stage } => {
+                Shader::from_glsl(device, source, stage, label)?
             }
+            ShaderSource::SpirV(bytes) => Shader::from_spirv(device, bytes, label)?,
             ShaderSource::Module(module) => Shader::from(module),
             ShaderSource::Struct(shader) => shader,
+            ShaderSource::Shared(shader) => return Ok(shader),
         };
 
-        let layout = ctx
-            .device()
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("render pipeline layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[],
-            });
-
-        let pipeline = ctx
-            .device()
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("render pipeline"),
-                layout: Some(&layout),
-                vertex: wgpu::VertexState {
-                    module: shader.raw(),
-                    entry_point: shader.vertex_entry(),
-                    buffers,
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: shader.raw(),
-                    entry_point: shader.fragment_entry(),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: ctx.surface_config().format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: Texture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-            });
+        Ok(Arc::new(shader))
+    }
+}
 
-        Self {
-            raw: pipeline,
-            layout,
-            shader,
+impl RenderPipeline {
+    /// Creates a pipeline with the default settings of [`RenderPipelineBuilder`]
+    pub fn new<'a>(
+        ctx: &WgpuContext,
+        shader: ShaderSource,
+        buffers: &'a [wgpu::VertexBufferLayout<'a>],
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let mut builder = RenderPipelineBuilder::new().vertex_buffers(buffers);
+        if let Some(label) = label {
+            builder = builder.label(label);
         }
+
+        builder.build(ctx, shader)
+    }
+
+    pub fn builder<'a>() -> RenderPipelineBuilder<'a> {
+        RenderPipelineBuilder::new()
     }
 
     pub fn raw(&self) -> &wgpu::RenderPipeline {
@@ -97,7 +77,374 @@ impl RenderPipeline {
         &self.layout
     }
 
+    /// Bind group layouts generated from the shader's reflection, indexed by group
+    ///
+    /// Empty if the layouts were given explicitly to the builder.
+    pub fn bind_group_layouts(&self) -> &[wgpu::BindGroupLayout] {
+        &self.bind_group_layouts
+    }
+
+    pub fn bind_group_layout(&self, group: u32) -> Option<&wgpu::BindGroupLayout> {
+        self.bind_group_layouts.get(group as usize)
+    }
+
+    /// Shader of the vertex stage, and of the fragment stage unless that one was separate
     pub fn shader(&self) -> &Shader {
         &self.shader
     }
+
+    /// Shader of the fragment stage
+    pub fn fragment_shader(&self) -> &Shader {
+        self.fragment_shader.as_deref().unwrap_or(&self.shader)
+    }
+}
+
+/// Configures a [`RenderPipeline`]
+///
+/// Defaults to a triangle list with counter-clockwise front faces and back
+/// face culling, a single color target in the context's color format with
+/// [`wgpu::BlendState::REPLACE`], a [`Texture::DEPTH_FORMAT`] depth test
+/// with [`wgpu::CompareFunction::Less`] and no multisampling. The shader's
+/// default entry points are used unless others are picked by name.
+///
+/// Unless bind group layouts are set explicitly, they are generated from the
+/// shader's reflection. Only bindings used by the pipeline's entry points end
+/// up in them, declared but unused ones don't need a bind group.
+///
+/// The builder only holds configuration, so it can be kept around and used
+/// to build several pipelines.
+#[derive(Debug, Clone)]
+pub struct RenderPipelineBuilder<'a> {
+    label: Option<String>,
+    vertex_entry: Option<String>,
+    fragment_entry: Option<String>,
+    no_fragment: bool,
+    vertex_buffers: Vec<wgpu::VertexBufferLayout<'a>>,
+    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    binding_types: BTreeMap<(u32, u32), wgpu::BindingType>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
+    blend: Option<wgpu::BlendState>,
+    color_targets: Option<Vec<Option<wgpu::ColorTargetState>>>,
+}
+
+impl<'a> Default for RenderPipelineBuilder<'a> {
+    fn default() -> Self {
+        Self {
+            label: None,
+            vertex_entry: None,
+            fragment_entry: None,
+            no_fragment: false,
+            vertex_buffers: Vec::new(),
+            bind_group_layouts: Vec::new(),
+            binding_types: BTreeMap::new(),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            blend: Some(wgpu::BlendState::REPLACE),
+            color_targets: None,
+        }
+    }
+}
+
+impl<'a> RenderPipelineBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Vertex entry point, [`Shader::vertex_entry`] if not set
+    pub fn vertex_entry(mut self, name: impl Into<String>) -> Self {
+        self.vertex_entry = Some(name.into());
+        self
+    }
+
+    /// Fragment entry point, [`Shader::fragment_entry`] if not set
+    pub fn fragment_entry(mut self, name: impl Into<String>) -> Self {
+        self.fragment_entry = Some(name.into());
+        self.no_fragment = false;
+        self
+    }
+
+    /// Leaves out the fragment stage, e.g. for depth prepasses
+    ///
+    /// The pipeline has no color targets then, only the depth/stencil state applies.
+    pub fn no_fragment(mut self) -> Self {
+        self.fragment_entry = None;
+        self.no_fragment = true;
+        self
+    }
+
+    pub fn vertex_buffers(mut self, buffers: &[wgpu::VertexBufferLayout<'a>]) -> Self {
+        self.vertex_buffers = buffers.to_vec();
+        self
+    }
+
+    /// Overrides the layouts generated from the shader's reflection
+    pub fn bind_group_layouts(mut self, layouts: &[&'a wgpu::BindGroupLayout]) -> Self {
+        self.bind_group_layouts = layouts.to_vec();
+        self
+    }
+
+    /// Replaces the reflected type of a binding in the generated layouts
+    ///
+    /// Reflection can't tell the texture format or how a sampler is used, so it
+    /// assumes filterable float textures and filtering samplers. E.g. an
+    /// `r32float` texture needs `Float { filterable: false }` here, and the
+    /// sampler used with it [`wgpu::SamplerBindingType::NonFiltering`].
+    pub fn binding_type(mut self, group: u32, binding: u32, ty: wgpu::BindingType) -> Self {
+        self.binding_types.insert((group, binding), ty);
+        self
+    }
+
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.primitive.topology = topology;
+        self
+    }
+
+    /// Index format of strip topologies, required if strips are drawn indexed
+    pub fn strip_index_format(mut self, format: Option<wgpu::IndexFormat>) -> Self {
+        self.primitive.strip_index_format = format;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.primitive.front_face = front_face;
+        self
+    }
+
+    /// Anything but [`wgpu::PolygonMode::Fill`] needs a device feature
+    pub fn polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.primitive.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Replaces the whole primitive state
+    pub fn primitive(mut self, primitive: wgpu::PrimitiveState) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
+    /// Blending of the default color target, ignored if color targets are set explicitly
+    pub fn blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Depth/stencil state, `None` disables the depth test entirely
+    pub fn depth_stencil(mut self, depth_stencil: Option<wgpu::DepthStencilState>) -> Self {
+        self.depth_stencil = depth_stencil;
+        self
+    }
+
+    /// Changes the compare function of the depth test, if there is one
+    pub fn depth_compare(mut self, compare: wgpu::CompareFunction) -> Self {
+        if let Some(depth_stencil) = &mut self.depth_stencil {
+            depth_stencil.depth_compare = compare;
+        }
+        self
+    }
+
+    /// Enables or disables depth writes, if there is a depth test
+    pub fn depth_write(mut self, enabled: bool) -> Self {
+        if let Some(depth_stencil) = &mut self.depth_stencil {
+            depth_stencil.depth_write_enabled = enabled;
+        }
+        self
+    }
+
+    pub fn multisample(mut self, multisample: wgpu::MultisampleState) -> Self {
+        self.multisample = multisample;
+        self
+    }
+
+    /// Color targets of the fragment stage, replacing the default single
+    /// target in the context's color format
+    pub fn color_targets(mut self, targets: &[Option<wgpu::ColorTargetState>]) -> Self {
+        self.color_targets = Some(targets.to_vec());
+        self
+    }
+
+    pub fn build(
+        &self,
+        ctx: &WgpuContext,
+        shader: ShaderSource,
+    ) -> Result<RenderPipeline, WgpuError> {
+        let shader_label = self
+            .label
+            .as_deref()
+            .map(|lbl| format!("shader for pipeline {lbl}"));
+        let shader = shader.into_shader(ctx.device(), shader_label.as_deref())?;
+
+        self.build_with(ctx, shader, None)
+    }
+
+    /// Builds the pipeline from separate vertex and fragment shaders, e.g. two GLSL files
+    pub fn build_separate(
+        &self,
+        ctx: &WgpuContext,
+        vertex: ShaderSource,
+        fragment: ShaderSource,
+    ) -> Result<RenderPipeline, WgpuError> {
+        let label = self.label.as_deref();
+        let vertex_label = label.map(|lbl| format!("vertex shader for pipeline {lbl}"));
+        let fragment_label = label.map(|lbl| format!("fragment shader for pipeline {lbl}"));
+        let vertex = vertex.into_shader(ctx.device(), vertex_label.as_deref())?;
+        let fragment = fragment.into_shader(ctx.device(), fragment_label.as_deref())?;
+
+        self.build_with(ctx, vertex, Some(fragment))
+    }
+
+    fn build_with(
+        &self,
+        ctx: &WgpuContext,
+        shader: Arc<Shader>,
+        separate_fragment: Option<Arc<Shader>>,
+    ) -> Result<RenderPipeline, WgpuError> {
+        let label = self.label.as_deref();
+        let fragment_shader = separate_fragment.as_deref().unwrap_or(&shader);
+
+        let vertex_entry = self
+            .vertex_entry
+            .as_deref()
+            .unwrap_or(shader.vertex_entry());
+        shader.check_entry_point(vertex_entry, naga::ShaderStage::Vertex)?;
+
+        let fragment_entry = match self.no_fragment {
+            true => None,
+            false => Some(
+                self.fragment_entry
+                    .as_deref()
+                    .unwrap_or(fragment_shader.fragment_entry()),
+            ),
+        };
+        if let Some(fragment_entry) = fragment_entry {
+            fragment_shader.check_entry_point(fragment_entry, naga::ShaderStage::Fragment)?;
+        }
+
+        if let Some(entry_point) = shader
+            .reflection()
+            .and_then(|reflection| reflection.entry_point(vertex_entry))
+        {
+            check_vertex_inputs(entry_point, &self.vertex_buffers, label)?;
+        }
+
+        let color_targets = match &self.color_targets {
+            Some(targets) => targets.clone(),
+            None => vec![Some(wgpu::ColorTargetState {
+                format: ctx.color_format(),
+                blend: self.blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        };
+
+        let (bind_group_layouts, layout, pipeline) = scope::capture(ctx.device(), label, || {
+            let reflected_entry_points = std::iter::once((&*shader, vertex_entry))
+                .chain(fragment_entry.map(|entry| (fragment_shader, entry)))
+                .map(|(shader, entry)| Some((shader.reflection()?, entry)))
+                .collect::<Option<Vec<_>>>();
+
+            let (bind_group_layouts, layout) = reflection::pipeline_layout(
+                ctx.device(),
+                reflected_entry_points.as_deref(),
+                &self.bind_group_layouts,
+                &self.binding_types,
+                "render pipeline layout",
+            )?;
+
+            let pipeline = ctx
+                .device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label.unwrap_or("render pipeline")),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: shader.raw(),
+                        entry_point: vertex_entry,
+                        buffers: &self.vertex_buffers,
+                    },
+                    fragment: fragment_entry.map(|entry_point| wgpu::FragmentState {
+                        module: fragment_shader.raw(),
+                        entry_point,
+                        targets: &color_targets,
+                    }),
+                    primitive: self.primitive,
+                    depth_stencil: self.depth_stencil.clone(),
+                    multisample: self.multisample,
+                    multiview: None,
+                });
+
+            Ok::<_, PipelineError>((bind_group_layouts, layout, pipeline))
+        })??;
+
+        Ok(RenderPipeline {
+            raw: pipeline,
+            layout,
+            bind_group_layouts,
+            shader,
+            fragment_shader: separate_fragment,
+        })
+    }
+}
+
+/// Makes sure every vertex input of the entry point is fed by an attribute of a matching format
+fn check_vertex_inputs(
+    entry_point: &EntryPoint,
+    buffers: &[wgpu::VertexBufferLayout],
+    label: Option<&str>,
+) -> Result<(), PipelineError> {
+    for input in &entry_point.vertex_inputs {
+        let attribute = buffers
+            .iter()
+            .flat_map(|buffer| buffer.attributes)
+            .find(|attribute| attribute.shader_location == input.location);
+
+        match attribute {
+            Some(attribute) if input.ty.accepts(attribute.format) => {}
+            Some(attribute) => {
+                return Err(PipelineError::VertexInputMismatch {
+                    label: label.map(str::to_string),
+                    location: input.location,
+                    expected: input.ty,
+                    provided: attribute.format,
+                })
+            }
+            None => {
+                return Err(PipelineError::MissingVertexInput {
+                    label: label.map(str::to_string),
+                    location: input.location,
+                    expected: input.ty,
+                })
+            }
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,589 @@
+use crate::wgpu::ShaderError;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Resolves `#include`, `#define` and conditional blocks in WGSL sources
+///
+/// Supported directives, each on its own line:
+/// - `#include "common/lighting.wgsl"` pastes a file relative to the shader root,
+///   every file is included at most once per shader
+/// - `#define NAME` and `#define NAME value`, the value replaces `NAME` in the following code
+/// - `#undef NAME`
+/// - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`
+///
+/// Values can also be handed in as WGSL constants, see [`Preprocessor::constant`].
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    root: PathBuf,
+    defines: HashMap<String, String>,
+    /// Sorted, so variants with the same constants produce the same code
+    constants: BTreeMap<String, String>,
+}
+
+/// Output of the [`Preprocessor`], WGSL code plus where each line came from
+#[derive(Debug, Clone)]
+pub struct PreprocessedSource {
+    code: String,
+    source_map: SourceMap,
+}
+
+/// Maps the lines of preprocessed code back to the original files
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    lines: Vec<LineOrigin>,
+}
+
+/// An original file that ended up in preprocessed code
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// `None` for code that didn't come from a file
+    pub path: Option<PathBuf>,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LineOrigin {
+    file: usize,
+    /// Byte offset of the line in the original file
+    offset: usize,
+    /// Byte offset of the line in the preprocessed code
+    output_offset: usize,
+}
+
+struct Conditional {
+    active: bool,
+    seen_else: bool,
+}
+
+impl Preprocessor {
+    /// Includes are resolved relative to `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            defines: HashMap::new(),
+            constants: BTreeMap::new(),
+        }
+    }
+
+    /// Defines `name` before processing, as if the source started with `#define name value`
+    ///
+    /// An empty value only makes `name` count as defined.
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.insert(name.into(), value.into());
+        self
+    }
+
+    /// Declares `const name = value;` ahead of the code, and defines `name` for `#ifdef`
+    ///
+    /// Unlike a define with a value, `name` is not replaced in the code, the
+    /// shader reads it as a constant of the type WGSL infers for `value`. That
+    /// makes it usable e.g. as an array size: `array<Light, LIGHT_COUNT>`.
+    pub fn constant(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        self.defines.insert(name.clone(), String::new());
+        self.constants.insert(name, value.into());
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Processes the file at `path`, relative to the shader root
+    pub fn process_file(&self, path: impl AsRef<Path>) -> Result<PreprocessedSource, ShaderError> {
+        let mut state = State::new(self);
+        state.declare_constants()?;
+        state.include(&self.root.join(path))?;
+        Ok(state.finish())
+    }
+
+    /// Processes source code that didn't come from a file
+    pub fn process_str(&self, source: &str) -> Result<PreprocessedSource, ShaderError> {
+        let mut state = State::new(self);
+        state.declare_constants()?;
+        state.process(source.to_string(), None)?;
+        Ok(state.finish())
+    }
+}
+
+impl PreprocessedSource {
+    /// The WGSL code to hand to naga
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+}
+
+impl SourceMap {
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Translates a span of the preprocessed code into the original file it came from
+    ///
+    /// Columns are only exact for lines without `#define` substitutions before the span.
+    pub fn locate(
+        &self,
+        span: naga::Span,
+    ) -> Option<(&SourceFile, naga::Span, naga::SourceLocation)> {
+        let range = span.to_range()?;
+        let index = self
+            .lines
+            .partition_point(|line| line.output_offset <= range.start)
+            .checked_sub(1)?;
+        let origin = self.lines[index];
+        let file = &self.files[origin.file];
+
+        let column = range.start - origin.output_offset;
+        let start = floor_char_boundary(&file.source, origin.offset + column);
+        let end = floor_char_boundary(&file.source, start + range.len());
+
+        let span = naga::Span::new(start as u32, end as u32);
+        let location = span.location(&file.source);
+
+        Some((file, span, location))
+    }
+}
+
+/// Processing state of a single shader
+struct State<'a> {
+    preprocessor: &'a Preprocessor,
+    defines: HashMap<String, String>,
+    included: HashSet<PathBuf>,
+    output: String,
+    source_map: SourceMap,
+}
+
+impl<'a> State<'a> {
+    fn new(preprocessor: &'a Preprocessor) -> Self {
+        Self {
+            preprocessor,
+            defines: preprocessor.defines.clone(),
+            included: HashSet::new(),
+            output: String::new(),
+            source_map: SourceMap::default(),
+        }
+    }
+
+    fn finish(self) -> PreprocessedSource {
+        PreprocessedSource {
+            code: self.output,
+            source_map: self.source_map,
+        }
+    }
+
+    /// Emits the declarations of the preprocessor's constants, as code that
+    /// didn't come from a file
+    fn declare_constants(&mut self) -> Result<(), ShaderError> {
+        if self.preprocessor.constants.is_empty() {
+            return Ok(());
+        }
+
+        let declarations = self
+            .preprocessor
+            .constants
+            .iter()
+            .map(|(name, value)| format!("const {name} = {value};\n"))
+            .collect();
+        self.process(declarations, None)
+    }
+
+    /// Processes the file at `path` unless it was included before
+    fn include(&mut self, path: &Path) -> Result<(), ShaderError> {
+        let source = std::fs::read_to_string(path).map_err(|source| ShaderError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if !self.included.insert(canonical) {
+            return Ok(());
+        }
+
+        self.process(source, Some(path.to_path_buf()))
+    }
+
+    fn process(&mut self, source: String, path: Option<PathBuf>) -> Result<(), ShaderError> {
+        let file = self.source_map.files.len();
+        self.source_map.files.push(SourceFile {
+            path: path.clone(),
+            source: String::new(),
+        });
+
+        let error = |line: u32, message: String| ShaderError::Preprocess {
+            path: path.clone(),
+            line,
+            message,
+        };
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut offset = 0;
+
+        for (index, line) in source.split_inclusive('\n').enumerate() {
+            let line_number = index as u32 + 1;
+            let line_offset = offset;
+            offset += line.len();
+
+            let active = conditionals.iter().all(|c| c.active);
+            let trimmed = line.trim();
+
+            let Some(directive) = trimmed.strip_prefix('#') else {
+                if active {
+                    self.source_map.lines.push(LineOrigin {
+                        file,
+                        offset: line_offset,
+                        output_offset: self.output.len(),
+                    });
+                    self.output.push_str(&substitute(line, &self.defines));
+                    if !line.ends_with('\n') {
+                        self.output.push('\n');
+                    }
+                }
+                continue;
+            };
+
+            let (name, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(name, arg)| (name, arg.trim()));
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined =
+                        self.defines
+                            .contains_key(identifier(argument, line_number, &error)?);
+                    conditionals.push(Conditional {
+                        active: defined == (name == "ifdef"),
+                        seen_else: false,
+                    });
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.seen_else => {
+                        conditional.active = !conditional.active;
+                        conditional.seen_else = true;
+                    }
+                    Some(_) => return Err(error(line_number, "duplicate #else".to_string())),
+                    None => return Err(error(line_number, "#else without #ifdef".to_string())),
+                },
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(error(line_number, "#endif without #ifdef".to_string()));
+                    }
+                }
+                // Everything below only applies in active blocks
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = argument
+                        .split_once(char::is_whitespace)
+                        .map_or((argument, ""), |(name, value)| (name, value.trim()));
+                    let define = identifier(define, line_number, &error)?;
+                    self.defines.insert(define.to_string(), value.to_string());
+                }
+                "undef" => {
+                    let define = identifier(argument, line_number, &error)?;
+                    self.defines.remove(define);
+                }
+                "include" => {
+                    let include = argument
+                        .strip_prefix('"')
+                        .and_then(|arg| arg.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            error(
+                                line_number,
+                                format!("expected a quoted path, found `{argument}`"),
+                            )
+                        })?;
+
+                    // Errors of nested includes already point at their own line
+                    self.include(&self.preprocessor.root.join(include))
+                        .map_err(|err| match err {
+                            ShaderError::Io { path, source } => error(
+                                line_number,
+                                format!("failed to include {}: {source}", path.display()),
+                            ),
+                            err => err,
+                        })?;
+                }
+                _ => return Err(error(line_number, format!("unknown directive `#{name}`"))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            let lines = source.lines().count() as u32;
+            return Err(error(lines, "missing #endif".to_string()));
+        }
+
+        self.source_map.files[file].source = source;
+        Ok(())
+    }
+}
+
+/// Substitutions shift columns, so mapped offsets can land inside a character
+fn floor_char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn identifier<'s>(
+    argument: &'s str,
+    line: u32,
+    error: &impl Fn(u32, String) -> ShaderError,
+) -> Result<&'s str, ShaderError> {
+    let valid = argument.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    match valid {
+        true => Ok(argument),
+        false => Err(error(line, format!("expected a name, found `{argument}`"))),
+    }
+}
+
+/// Replaces every identifier that is defined with a value
+fn substitute(line: &str, defines: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        // Letters right after a digit belong to a number like `1e5` or `2u`
+        let in_number = rest[..start].ends_with(|c: char| c.is_ascii_digit());
+
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+
+        match defines.get(word) {
+            Some(value) if !value.is_empty() && !in_number => output.push_str(value),
+            _ => output.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `files` into a fresh shader root in the temp directory
+    fn shader_root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "renderer-preprocessor-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+
+        for (path, source) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+
+        root
+    }
+
+    fn process(source: &str) -> String {
+        Preprocessor::new(".")
+            .process_str(source)
+            .unwrap()
+            .code()
+            .to_string()
+    }
+
+    /// Line and message of a preprocessing error
+    fn error(result: Result<PreprocessedSource, ShaderError>) -> (Option<PathBuf>, u32, String) {
+        match result {
+            Err(ShaderError::Preprocess {
+                path,
+                line,
+                message,
+            }) => (path, line, message),
+            Err(err) => panic!("expected a preprocessing error, got {err}"),
+            Ok(source) => panic!("expected a preprocessing error, got {}", source.code()),
+        }
+    }
+
+    #[test]
+    fn include_pastes_files_once() {
+        let root = shader_root(
+            "include",
+            &[
+                (
+                    "main.wgsl",
+                    "#include \"common/a.wgsl\"\n#include \"common/a.wgsl\"\nmain\n",
+                ),
+                ("common/a.wgsl", "a\n"),
+            ],
+        );
+
+        let source = Preprocessor::new(&root).process_file("main.wgsl").unwrap();
+        assert_eq!(source.code(), "a\nmain\n");
+    }
+
+    #[test]
+    fn missing_include_points_at_directive() {
+        let root = shader_root("missing", &[("main.wgsl", "\n#include \"nope.wgsl\"\n")]);
+
+        let (path, line, message) = error(Preprocessor::new(&root).process_file("main.wgsl"));
+        assert_eq!(path, Some(root.join("main.wgsl")));
+        assert_eq!(line, 2);
+        assert!(message.starts_with("failed to include"), "{message}");
+    }
+
+    #[test]
+    fn define_substitutes_whole_identifiers() {
+        let code = process("#define COUNT 4u\nlet a = COUNT + COUNTER + 2COUNT;\n");
+        assert_eq!(code, "let a = 4u + COUNTER + 2COUNT;\n");
+    }
+
+    #[test]
+    fn defines_apply_from_their_line_on() {
+        let code = process("COUNT\n#define COUNT 4\nCOUNT\n#undef COUNT\nCOUNT\n");
+        assert_eq!(code, "COUNT\n4\nCOUNT\n");
+    }
+
+    #[test]
+    fn preprocessor_defines_apply_before_source() {
+        let code = Preprocessor::new(".")
+            .define("COUNT", "8")
+            .define("SHADOWS", "")
+            .process_str("#ifdef SHADOWS\nCOUNT\n#endif\n")
+            .unwrap();
+        assert_eq!(code.code(), "8\n");
+    }
+
+    #[test]
+    fn constants_are_declared_not_substituted() {
+        let code = Preprocessor::new(".")
+            .constant("LIGHT_COUNT", "4u")
+            .constant("EXPOSURE", "1.5")
+            .process_str(
+                "#ifdef LIGHT_COUNT\nvar<private> lights: array<Light, LIGHT_COUNT>;\n#endif\n",
+            )
+            .unwrap();
+
+        assert_eq!(
+            code.code(),
+            "const EXPOSURE = 1.5;\nconst LIGHT_COUNT = 4u;\nvar<private> lights: array<Light, LIGHT_COUNT>;\n"
+        );
+    }
+
+    #[test]
+    fn undef_ends_ifdef() {
+        let code = process("#define A\n#undef A\n#ifdef A\na\n#else\nnot a\n#endif\n");
+        assert_eq!(code, "not a\n");
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let source = "\
+#define A
+#ifdef A
+a
+#ifndef B
+not b
+#ifdef C
+c
+#else
+not c
+#endif
+#else
+b
+#endif
+#else
+not a
+#endif
+";
+        assert_eq!(process(source), "a\nnot b\nnot c\n");
+    }
+
+    #[test]
+    fn directives_in_inactive_blocks_are_ignored() {
+        let code =
+            process("#ifdef A\n#define B\n#include \"nope.wgsl\"\n#endif\n#ifdef B\nb\n#endif\n");
+        assert_eq!(code, "");
+    }
+
+    #[test]
+    fn unbalanced_directives_are_errors() {
+        let cases = [
+            ("a\n#else\n", 2, "#else without #ifdef"),
+            ("#ifdef A\n#else\n#else\n#endif\n", 3, "duplicate #else"),
+            ("#endif\n", 1, "#endif without #ifdef"),
+            ("#ifdef A\n#ifdef B\n#endif\na\n", 4, "missing #endif"),
+            ("\n\n#frobnicate\n", 3, "unknown directive `#frobnicate`"),
+            ("#define 1A\n", 1, "expected a name, found `1A`"),
+        ];
+
+        for (source, expected_line, expected_message) in cases {
+            let (_, line, message) = error(Preprocessor::new(".").process_str(source));
+            assert_eq!((line, message.as_str()), (expected_line, expected_message));
+        }
+    }
+
+    #[test]
+    fn errors_in_includes_point_at_the_included_file() {
+        let root = shader_root(
+            "include-error",
+            &[
+                ("main.wgsl", "main\n#include \"common/bad.wgsl\"\n"),
+                ("common/bad.wgsl", "fine\n\n#else\n"),
+            ],
+        );
+
+        let (path, line, _) = error(Preprocessor::new(&root).process_file("main.wgsl"));
+        assert_eq!(path, Some(root.join("common/bad.wgsl")));
+        assert_eq!(line, 3);
+    }
+
+    #[test]
+    fn source_map_locates_lines_in_included_files() {
+        let root = shader_root(
+            "source-map",
+            &[
+                (
+                    "main.wgsl",
+                    "#include \"common/light.wgsl\"\n\nfn main() {}\n",
+                ),
+                (
+                    "common/light.wgsl",
+                    "// lights\n#ifdef NOPE\nskipped\n#endif\nfn light() {}\n",
+                ),
+            ],
+        );
+
+        let source = Preprocessor::new(&root).process_file("main.wgsl").unwrap();
+        assert_eq!(source.code(), "// lights\nfn light() {}\n\nfn main() {}\n");
+
+        let locate = |needle: &str| {
+            let start = source.code().find(needle).unwrap();
+            let span = naga::Span::new(start as u32, (start + needle.len()) as u32);
+            let (file, span, location) = source.source_map().locate(span).unwrap();
+            (
+                file.path.clone().unwrap(),
+                &file.source[span.to_range().unwrap()],
+                location.line_number,
+                location.line_position,
+            )
+        };
+
+        assert_eq!(
+            locate("light()"),
+            (root.join("common/light.wgsl"), "light()", 5, 4)
+        );
+        assert_eq!(locate("main"), (root.join("main.wgsl"), "main", 3, 4));
+    }
+}
@@ -0,0 +1,652 @@
+use crate::wgpu::PipelineError;
+use std::collections::BTreeMap;
+use std::num::{NonZeroU32, NonZeroU64};
+
+/// What a shader module declares, gathered from its naga IR
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub entry_points: Vec<EntryPoint>,
+    /// Every resource binding declared in the module, used or not
+    pub bindings: Vec<ResourceBinding>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: naga::ShaderStage,
+    /// `[0, 0, 0]` for anything but compute entry points
+    pub workgroup_size: [u32; 3],
+    /// `@location` inputs, only filled for vertex entry points
+    pub vertex_inputs: Vec<VertexInput>,
+    /// `(group, binding)` of every resource this entry point accesses
+    pub used_bindings: Vec<(u32, u32)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VertexInput {
+    pub location: u32,
+    pub name: Option<String>,
+    pub ty: VertexInputType,
+}
+
+/// Numeric type of a vertex input, e.g. `vec3<f32>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexInputType {
+    pub kind: naga::ScalarKind,
+    /// Width of a single component in bytes
+    pub width: u8,
+    pub components: u32,
+}
+
+impl VertexInputType {
+    /// Whether a vertex attribute of the given format can feed this input
+    ///
+    /// The scalar kind and the number of components have to match,
+    /// normalized formats count as floats.
+    pub fn accepts(&self, format: wgpu::VertexFormat) -> bool {
+        use wgpu::VertexFormat as Vf;
+
+        let (kind, components) = match format {
+            Vf::Uint8x2 | Vf::Uint16x2 | Vf::Uint32x2 => (naga::ScalarKind::Uint, 2),
+            Vf::Uint8x4 | Vf::Uint16x4 | Vf::Uint32x4 => (naga::ScalarKind::Uint, 4),
+            Vf::Uint32 => (naga::ScalarKind::Uint, 1),
+            Vf::Uint32x3 => (naga::ScalarKind::Uint, 3),
+            Vf::Sint8x2 | Vf::Sint16x2 | Vf::Sint32x2 => (naga::ScalarKind::Sint, 2),
+            Vf::Sint8x4 | Vf::Sint16x4 | Vf::Sint32x4 => (naga::ScalarKind::Sint, 4),
+            Vf::Sint32 => (naga::ScalarKind::Sint, 1),
+            Vf::Sint32x3 => (naga::ScalarKind::Sint, 3),
+            Vf::Float32 | Vf::Float64 => (naga::ScalarKind::Float, 1),
+            Vf::Float32x3 | Vf::Float64x3 => (naga::ScalarKind::Float, 3),
+            Vf::Unorm8x2
+            | Vf::Snorm8x2
+            | Vf::Unorm16x2
+            | Vf::Snorm16x2
+            | Vf::Float16x2
+            | Vf::Float32x2
+            | Vf::Float64x2 => (naga::ScalarKind::Float, 2),
+            Vf::Unorm8x4
+            | Vf::Snorm8x4
+            | Vf::Unorm16x4
+            | Vf::Snorm16x4
+            | Vf::Float16x4
+            | Vf::Float32x4
+            | Vf::Float64x4 => (naga::ScalarKind::Float, 4),
+        };
+        let double = matches!(
+            format,
+            Vf::Float64 | Vf::Float64x2 | Vf::Float64x3 | Vf::Float64x4
+        );
+
+        self.kind == kind && self.components == components && (self.width == 8) == double
+    }
+}
+
+impl std::fmt::Display for VertexInputType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scalar = match (self.kind, self.width) {
+            (naga::ScalarKind::Float, 2) => "f16",
+            (naga::ScalarKind::Float, 8) => "f64",
+            (naga::ScalarKind::Float, _) => "f32",
+            (naga::ScalarKind::Sint, _) => "i32",
+            (naga::ScalarKind::Uint, _) => "u32",
+            (naga::ScalarKind::Bool, _) => "bool",
+        };
+
+        match self.components {
+            1 => write!(f, "{scalar}"),
+            n => write!(f, "vec{n}<{scalar}>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResourceBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    pub ty: wgpu::BindingType,
+    /// Element count for binding arrays
+    pub count: Option<NonZeroU32>,
+}
+
+impl ShaderReflection {
+    pub fn new(module: &naga::Module, info: &naga::valid::ModuleInfo) -> Self {
+        let bindings = module
+            .global_variables
+            .iter()
+            .filter_map(|(_, var)| {
+                let binding = var.binding.as_ref()?;
+                let (ty, count) = binding_type(module, var)?;
+
+                Some(ResourceBinding {
+                    group: binding.group,
+                    binding: binding.binding,
+                    name: var.name.clone(),
+                    ty,
+                    count,
+                })
+            })
+            .collect();
+
+        let entry_points = module
+            .entry_points
+            .iter()
+            .enumerate()
+            .map(|(index, ep)| {
+                let function_info = info.get_entry_point(index);
+                let used_bindings = module
+                    .global_variables
+                    .iter()
+                    .filter(|(handle, _)| !function_info[*handle].is_empty())
+                    .filter_map(|(_, var)| var.binding.as_ref())
+                    .map(|binding| (binding.group, binding.binding))
+                    .collect();
+
+                let vertex_inputs = match ep.stage {
+                    naga::ShaderStage::Vertex => vertex_inputs(module, &ep.function),
+                    _ => Vec::new(),
+                };
+
+                EntryPoint {
+                    name: ep.name.clone(),
+                    stage: ep.stage,
+                    workgroup_size: ep.workgroup_size,
+                    vertex_inputs,
+                    used_bindings,
+                }
+            })
+            .collect();
+
+        Self {
+            entry_points,
+            bindings,
+        }
+    }
+
+    pub fn entry_point(&self, name: &str) -> Option<&EntryPoint> {
+        self.entry_points.iter().find(|ep| ep.name == name)
+    }
+
+    pub fn binding(&self, group: u32, binding: u32) -> Option<&ResourceBinding> {
+        self.bindings
+            .iter()
+            .find(|b| b.group == group && b.binding == binding)
+    }
+
+    /// Bind group layout entries for a pipeline made of the given entry points,
+    /// indexed by group.
+    ///
+    /// The entry points can come from different modules, e.g. a vertex and a
+    /// fragment shader compiled separately. Only bindings that one of them
+    /// actually uses are included, visible to the stages that use them. Groups
+    /// without used bindings in between get an empty list, trailing ones are
+    /// left out.
+    ///
+    /// `overrides` replace the reflected type of a `(group, binding)`, for what
+    /// the shader can't tell, e.g. that a texture has an unfilterable format.
+    /// Modules declaring the same binding with different types are an error,
+    /// unless they are buffers that only differ in their minimum size.
+    pub fn bind_group_layout_entries(
+        entry_points: &[(&ShaderReflection, &str)],
+        overrides: &BTreeMap<(u32, u32), wgpu::BindingType>,
+    ) -> Result<Vec<Vec<wgpu::BindGroupLayoutEntry>>, PipelineError> {
+        let mut entries: BTreeMap<(u32, u32), wgpu::BindGroupLayoutEntry> = BTreeMap::new();
+        for (reflection, name) in entry_points {
+            let Some(ep) = reflection.entry_point(name) else {
+                continue;
+            };
+
+            for &(group, binding) in &ep.used_bindings {
+                // Can't be None, used bindings come from the same global variables
+                let Some(resource) = reflection.binding(group, binding) else {
+                    continue;
+                };
+                let ty = overrides
+                    .get(&(group, binding))
+                    .copied()
+                    .unwrap_or(resource.ty);
+
+                let entry = entries
+                    .entry((group, binding))
+                    .or_insert(wgpu::BindGroupLayoutEntry {
+                        binding,
+                        visibility: wgpu::ShaderStages::NONE,
+                        ty,
+                        count: resource.count,
+                    });
+                entry.visibility |= shader_stages(ep.stage);
+                entry.ty = merge_binding_types(entry.ty, ty)
+                    .filter(|_| entry.count == resource.count)
+                    .ok_or(PipelineError::ConflictingBinding {
+                        group,
+                        binding,
+                        first: entry.ty,
+                        second: ty,
+                    })?;
+            }
+        }
+
+        let group_count = entries
+            .keys()
+            .map(|(group, _)| group + 1)
+            .max()
+            .unwrap_or(0);
+        let mut groups = vec![Vec::new(); group_count as usize];
+
+        for ((group, _), entry) in entries {
+            groups[group as usize].push(entry);
+        }
+
+        Ok(groups)
+    }
+}
+
+/// The type covering both declarations of a binding, `None` if they don't match
+fn merge_binding_types(
+    first: wgpu::BindingType,
+    second: wgpu::BindingType,
+) -> Option<wgpu::BindingType> {
+    match (first, second) {
+        _ if first == second => Some(first),
+        (
+            wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset,
+                min_binding_size,
+            },
+            wgpu::BindingType::Buffer {
+                ty: second_ty,
+                has_dynamic_offset: second_dynamic_offset,
+                min_binding_size: second_min_size,
+            },
+        ) if ty == second_ty && has_dynamic_offset == second_dynamic_offset => {
+            Some(wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset,
+                min_binding_size: min_binding_size.max(second_min_size),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Creates the layout of a pipeline made of the given entry points
+///
+/// Unless `explicit` layouts are given, or one of the entry points has no
+/// reflection, the bind group layouts are generated with
+/// [`ShaderReflection::bind_group_layout_entries`] and returned along the
+/// pipeline layout, otherwise the returned list is empty.
+pub(crate) fn pipeline_layout(
+    device: &wgpu::Device,
+    entry_points: Option<&[(&ShaderReflection, &str)]>,
+    explicit: &[&wgpu::BindGroupLayout],
+    overrides: &BTreeMap<(u32, u32), wgpu::BindingType>,
+    label: &str,
+) -> Result<(Vec<wgpu::BindGroupLayout>, wgpu::PipelineLayout), PipelineError> {
+    let bind_group_layouts: Vec<_> = match entry_points {
+        Some(entry_points) if explicit.is_empty() => {
+            ShaderReflection::bind_group_layout_entries(entry_points, overrides)?
+                .iter()
+                .map(|entries| {
+                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: Some("reflected bind group layout"),
+                        entries,
+                    })
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts: &if bind_group_layouts.is_empty() {
+            explicit.to_vec()
+        } else {
+            bind_group_layouts.iter().collect()
+        },
+        push_constant_ranges: &[],
+    });
+
+    Ok((bind_group_layouts, layout))
+}
+
+pub(crate) fn shader_stages(stage: naga::ShaderStage) -> wgpu::ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+    }
+}
+
+fn vertex_inputs(module: &naga::Module, function: &naga::Function) -> Vec<VertexInput> {
+    let mut inputs = Vec::new();
+
+    for arg in &function.arguments {
+        match &arg.binding {
+            Some(naga::Binding::Location { location, .. }) => {
+                if let Some(ty) = vertex_input_type(&module.types[arg.ty].inner) {
+                    inputs.push(VertexInput {
+                        location: *location,
+                        name: arg.name.clone(),
+                        ty,
+                    });
+                }
+            }
+            Some(naga::Binding::BuiltIn(_)) => {}
+            None => {
+                // Inputs grouped in a struct carry their bindings on the members
+                if let naga::TypeInner::Struct { members, .. } = &module.types[arg.ty].inner {
+                    for member in members {
+                        let Some(naga::Binding::Location { location, .. }) = member.binding else {
+                            continue;
+                        };
+                        if let Some(ty) = vertex_input_type(&module.types[member.ty].inner) {
+                            inputs.push(VertexInput {
+                                location,
+                                name: member.name.clone(),
+                                ty,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    inputs.sort_by_key(|input| input.location);
+    inputs
+}
+
+fn vertex_input_type(inner: &naga::TypeInner) -> Option<VertexInputType> {
+    match *inner {
+        naga::TypeInner::Scalar { kind, width } => Some(VertexInputType {
+            kind,
+            width,
+            components: 1,
+        }),
+        naga::TypeInner::Vector { size, kind, width } => Some(VertexInputType {
+            kind,
+            width,
+            components: size as u32,
+        }),
+        _ => None,
+    }
+}
+
+fn binding_type(
+    module: &naga::Module,
+    var: &naga::GlobalVariable,
+) -> Option<(wgpu::BindingType, Option<NonZeroU32>)> {
+    let (inner, count) = match &module.types[var.ty].inner {
+        naga::TypeInner::BindingArray { base, size } => {
+            let count = match size {
+                naga::ArraySize::Constant(handle) => match module.constants[*handle].inner {
+                    naga::ConstantInner::Scalar {
+                        value: naga::ScalarValue::Uint(len),
+                        ..
+                    } => u32::try_from(len).ok().and_then(NonZeroU32::new),
+                    naga::ConstantInner::Scalar {
+                        value: naga::ScalarValue::Sint(len),
+                        ..
+                    } => u32::try_from(len).ok().and_then(NonZeroU32::new),
+                    _ => None,
+                },
+                naga::ArraySize::Dynamic => None,
+            };
+            (&module.types[*base].inner, count)
+        }
+        inner => (inner, None),
+    };
+
+    let ty = match var.space {
+        naga::AddressSpace::Uniform => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(inner.size(&module.constants) as u64),
+        },
+        naga::AddressSpace::Storage { access } => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(naga::StorageAccess::STORE),
+            },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        naga::AddressSpace::Handle => match *inner {
+            naga::TypeInner::Sampler { comparison: true } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+            }
+            naga::TypeInner::Sampler { comparison: false } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+            }
+            naga::TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            } => {
+                let view_dimension = view_dimension(dim, arrayed);
+                match class {
+                    naga::ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                        sample_type: match kind {
+                            naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            _ => wgpu::TextureSampleType::Float { filterable: !multi },
+                        },
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Storage { format, access } => {
+                        wgpu::BindingType::StorageTexture {
+                            access: if access
+                                .contains(naga::StorageAccess::LOAD | naga::StorageAccess::STORE)
+                            {
+                                wgpu::StorageTextureAccess::ReadWrite
+                            } else if access.contains(naga::StorageAccess::STORE) {
+                                wgpu::StorageTextureAccess::WriteOnly
+                            } else {
+                                wgpu::StorageTextureAccess::ReadOnly
+                            },
+                            format: storage_format(format),
+                            view_dimension,
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some((ty, count))
+}
+
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+fn storage_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as Sf;
+    use wgpu::TextureFormat as Tf;
+
+    match format {
+        Sf::R8Unorm => Tf::R8Unorm,
+        Sf::R8Snorm => Tf::R8Snorm,
+        Sf::R8Uint => Tf::R8Uint,
+        Sf::R8Sint => Tf::R8Sint,
+        Sf::R16Uint => Tf::R16Uint,
+        Sf::R16Sint => Tf::R16Sint,
+        Sf::R16Float => Tf::R16Float,
+        Sf::Rg8Unorm => Tf::Rg8Unorm,
+        Sf::Rg8Snorm => Tf::Rg8Snorm,
+        Sf::Rg8Uint => Tf::Rg8Uint,
+        Sf::Rg8Sint => Tf::Rg8Sint,
+        Sf::R32Uint => Tf::R32Uint,
+        Sf::R32Sint => Tf::R32Sint,
+        Sf::R32Float => Tf::R32Float,
+        Sf::Rg16Uint => Tf::Rg16Uint,
+        Sf::Rg16Sint => Tf::Rg16Sint,
+        Sf::Rg16Float => Tf::Rg16Float,
+        Sf::Rgba8Unorm => Tf::Rgba8Unorm,
+        Sf::Rgba8Snorm => Tf::Rgba8Snorm,
+        Sf::Rgba8Uint => Tf::Rgba8Uint,
+        Sf::Rgba8Sint => Tf::Rgba8Sint,
+        Sf::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        Sf::Rg11b10Float => Tf::Rg11b10Float,
+        Sf::Rg32Uint => Tf::Rg32Uint,
+        Sf::Rg32Sint => Tf::Rg32Sint,
+        Sf::Rg32Float => Tf::Rg32Float,
+        Sf::Rgba16Uint => Tf::Rgba16Uint,
+        Sf::Rgba16Sint => Tf::Rgba16Sint,
+        Sf::Rgba16Float => Tf::Rgba16Float,
+        Sf::Rgba32Uint => Tf::Rgba32Uint,
+        Sf::Rgba32Sint => Tf::Rgba32Sint,
+        Sf::Rgba32Float => Tf::Rgba32Float,
+        Sf::R16Unorm => Tf::R16Unorm,
+        Sf::R16Snorm => Tf::R16Snorm,
+        Sf::Rg16Unorm => Tf::Rg16Unorm,
+        Sf::Rg16Snorm => Tf::Rg16Snorm,
+        Sf::Rgba16Unorm => Tf::Rgba16Unorm,
+        Sf::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reflect(source: &str) -> ShaderReflection {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap();
+        ShaderReflection::new(&module, &info)
+    }
+
+    const VERTEX: &str = r"
+        @group(0) @binding(0) var<uniform> camera: vec4<f32>;
+
+        @vertex
+        fn vs_main() -> @builtin(position) vec4<f32> {
+            return camera;
+        }
+    ";
+
+    #[test]
+    fn buffers_of_different_sizes_are_merged() {
+        let vertex = reflect(VERTEX);
+        let fragment = reflect(
+            r"
+            @group(0) @binding(0) var<uniform> camera: mat4x4<f32>;
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                return camera[0];
+            }
+        ",
+        );
+
+        let groups = ShaderReflection::bind_group_layout_entries(
+            &[(&vertex, "vs_main"), (&fragment, "fs_main")],
+            &BTreeMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            groups[0][0].visibility,
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT
+        );
+        assert_eq!(
+            groups[0][0].ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(64),
+            }
+        );
+    }
+
+    #[test]
+    fn conflicting_declarations_are_an_error() {
+        let vertex = reflect(VERTEX);
+        let fragment = reflect(
+            r"
+            @group(0) @binding(0) var color: texture_2d<f32>;
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                return textureLoad(color, vec2<i32>(0), 0);
+            }
+        ",
+        );
+
+        let result = ShaderReflection::bind_group_layout_entries(
+            &[(&vertex, "vs_main"), (&fragment, "fs_main")],
+            &BTreeMap::new(),
+        );
+
+        match result {
+            Err(PipelineError::ConflictingBinding {
+                group: 0,
+                binding: 0,
+                first: wgpu::BindingType::Buffer { .. },
+                second: wgpu::BindingType::Texture { .. },
+            }) => {}
+            result => panic!("expected conflicting bindings, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn overrides_replace_reflected_types() {
+        let compute = reflect(
+            r"
+            @group(0) @binding(0) var depth: texture_2d<f32>;
+            @group(0) @binding(1) var depth_sampler: sampler;
+            @group(1) @binding(0) var<storage, read_write> out: f32;
+
+            @compute @workgroup_size(1)
+            fn cs_main() {
+                out = textureSampleLevel(depth, depth_sampler, vec2<f32>(0.5), 0.0).r;
+            }
+        ",
+        );
+        let unfilterable = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let non_filtering = wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering);
+
+        let reflected =
+            ShaderReflection::bind_group_layout_entries(&[(&compute, "cs_main")], &BTreeMap::new())
+                .unwrap();
+        let overridden = ShaderReflection::bind_group_layout_entries(
+            &[(&compute, "cs_main")],
+            &BTreeMap::from([((0, 0), unfilterable), ((0, 1), non_filtering)]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            reflected[0][1].ty,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+        );
+        assert_eq!(overridden[0][0].ty, unfilterable);
+        assert_eq!(overridden[0][1].ty, non_filtering);
+        assert_eq!(overridden[1], reflected[1]);
+    }
+}
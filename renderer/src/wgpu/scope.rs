@@ -0,0 +1,24 @@
+use crate::wgpu::WgpuError;
+
+/// Runs `f` inside validation and out-of-memory error scopes, turning errors
+/// wgpu reports for the created resources into [`WgpuError`]s instead of
+/// passing them to the uncaptured error handler.
+pub(crate) fn capture<T>(
+    device: &wgpu::Device,
+    label: Option<&str>,
+    f: impl FnOnce() -> T,
+) -> Result<T, WgpuError> {
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let value = f();
+
+    // Scopes have to be popped in reverse order, even if the first one already has an error
+    let validation = beul::execute(device.pop_error_scope());
+    let out_of_memory = beul::execute(device.pop_error_scope());
+
+    match validation.or(out_of_memory) {
+        None => Ok(value),
+        Some(err) => Err(WgpuError::from_device_error(err, label)),
+    }
+}
@@ -1,40 +1,376 @@
+use crate::wgpu::scope;
+use crate::wgpu::{
+    PreprocessedSource, Preprocessor, ShaderDiagnostic, ShaderError, ShaderReflection, WgpuError,
+};
+use std::borrow::Cow;
+use std::path::Path;
 use wgpu::ShaderModule;
 
 /// Wrapper for a wgpu shader module
 ///
-/// > Only supports WGSL for now
+/// WGSL, GLSL and SPIR-V sources are all parsed and validated with naga, so
+/// they share reflection and error reporting.
+///
+/// A module can hold any number of entry points, pipelines pick theirs by
+/// name and fall back to [`Shader::vertex_entry`], [`Shader::fragment_entry`]
+/// and [`Shader::compute_entry`].
 pub struct Shader {
     raw: wgpu::ShaderModule,
+    label: Option<String>,
+    reflection: Option<ShaderReflection>,
 }
 
 impl Shader {
     pub const VERTEX_ENTRY: &'static str = "vs_main";
     pub const FRAGMENT_ENTRY: &'static str = "fs_main";
+    pub const COMPUTE_ENTRY: &'static str = "cs_main";
+
+    /// Parses and validates the WGSL source with naga before creating the module
+    pub fn new(
+        device: &wgpu::Device,
+        source: impl AsRef<str>,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let source = source.as_ref();
+        let module = Self::parse_wgsl(source, label)?;
+        Self::from_naga(device, module, Some(source), label)
+    }
+
+    /// Parses GLSL source for a single stage, its entry point is called `main`
+    pub fn from_glsl(
+        device: &wgpu::Device,
+        source: impl AsRef<str>,
+        stage: naga::ShaderStage,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let source = source.as_ref();
+        let module = Self::parse_glsl(source, stage, label)?;
+        Self::from_naga(device, module, Some(source), label)
+    }
+
+    /// Parses a SPIR-V binary, `bytes` has to be a whole number of 32-bit words
+    pub fn from_spirv(
+        device: &wgpu::Device,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let module = Self::parse_spirv(bytes, label)?;
+        Self::from_naga(device, module, None, label)
+    }
+
+    /// Validates a parsed module and creates the wgpu module from it
+    ///
+    /// `source` is only used to locate validation errors.
+    fn from_naga(
+        device: &wgpu::Device,
+        module: naga::Module,
+        source: Option<&str>,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let reflection = Self::validate(&module, source, label)?;
+
+        let shader = scope::capture(device, label, || {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label,
+                source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+            })
+        })?;
+
+        Ok(Self {
+            raw: shader,
+            label: label.map(str::to_string),
+            reflection: Some(reflection),
+        })
+    }
+
+    /// Creates the module from the output of the [`Preprocessor`](crate::wgpu::Preprocessor)
+    ///
+    /// Parse and validation errors point back to the original file and line.
+    pub fn from_preprocessed(
+        device: &wgpu::Device,
+        source: &PreprocessedSource,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        Self::new(device, source.code(), label).map_err(|err| match err {
+            WgpuError::Shader(err) => WgpuError::Shader(remap_error(err, source)),
+            err => err,
+        })
+    }
 
-    pub fn new(device: &wgpu::Device, source: impl AsRef<str>, label: Option<&str>) -> Self {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label,
-            source: wgpu::ShaderSource::Wgsl(source.as_ref().into()),
-        });
+    /// Loads a shader from disk, the path is used as label
+    ///
+    /// The language is picked by extension: `.wgsl` for WGSL, `.vert`, `.frag`
+    /// and `.comp` for GLSL of that stage and `.spv` for SPIR-V.
+    pub fn from_path(device: &wgpu::Device, path: impl AsRef<Path>) -> Result<Self, WgpuError> {
+        let path = path.as_ref();
+        let label = path.display().to_string();
+        let io_error = |source| ShaderError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
 
-        Self { raw: shader }
+        match Language::from_path(path)? {
+            Language::Wgsl => {
+                let source = std::fs::read_to_string(path).map_err(io_error)?;
+                Self::new(device, source, Some(&label))
+            }
+            Language::Glsl(stage) => {
+                let source = std::fs::read_to_string(path).map_err(io_error)?;
+                Self::from_glsl(device, source, stage, Some(&label))
+            }
+            Language::SpirV => {
+                let bytes = std::fs::read(path).map_err(io_error)?;
+                Self::from_spirv(device, &bytes, Some(&label))
+            }
+        }
+    }
+
+    /// Parses and validates a shader file without creating a module, so no device is needed
+    ///
+    /// `path` is relative to the preprocessor's root and the language is picked
+    /// like in [`Shader::from_path`]. WGSL goes through the preprocessor first.
+    pub fn validate_file(
+        preprocessor: &Preprocessor,
+        path: impl AsRef<Path>,
+    ) -> Result<ShaderReflection, ShaderError> {
+        let path = path.as_ref();
+        let label = path.display().to_string();
+        let full_path = preprocessor.root().join(path);
+        let io_error = |source| ShaderError::Io {
+            path: full_path.clone(),
+            source,
+        };
+
+        match Language::from_path(path)? {
+            Language::Wgsl => {
+                let source = preprocessor.process_file(path)?;
+                Self::parse_wgsl(source.code(), Some(&label))
+                    .and_then(|module| Self::validate(&module, Some(source.code()), Some(&label)))
+                    .map_err(|err| remap_error(err, &source))
+            }
+            Language::Glsl(stage) => {
+                let source = std::fs::read_to_string(&full_path).map_err(io_error)?;
+                let module = Self::parse_glsl(&source, stage, Some(&label))?;
+                Self::validate(&module, Some(&source), Some(&label))
+            }
+            Language::SpirV => {
+                let bytes = std::fs::read(&full_path).map_err(io_error)?;
+                let module = Self::parse_spirv(&bytes, Some(&label))?;
+                Self::validate(&module, None, Some(&label))
+            }
+        }
     }
 
     pub fn raw(&self) -> &wgpu::ShaderModule {
         &self.raw
     }
 
+    /// Entry points, vertex inputs and resource bindings of the shader
+    ///
+    /// `None` if the shader was created from a raw [`wgpu::ShaderModule`].
+    pub fn reflection(&self) -> Option<&ShaderReflection> {
+        self.reflection.as_ref()
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Default vertex entry point of pipelines using this shader
+    ///
+    /// [`Shader::VERTEX_ENTRY`], unless the module lacks it and has exactly one
+    /// vertex entry point, like `main` of a GLSL vertex shader.
     pub fn vertex_entry(&self) -> &str {
-        Self::VERTEX_ENTRY
+        self.default_entry(naga::ShaderStage::Vertex, Self::VERTEX_ENTRY)
     }
 
+    /// Default fragment entry point of pipelines using this shader
+    ///
+    /// [`Shader::FRAGMENT_ENTRY`], unless the module lacks it and has exactly one
+    /// fragment entry point.
     pub fn fragment_entry(&self) -> &str {
-        Self::FRAGMENT_ENTRY
+        self.default_entry(naga::ShaderStage::Fragment, Self::FRAGMENT_ENTRY)
+    }
+
+    /// Default entry point of compute pipelines using this shader
+    ///
+    /// [`Shader::COMPUTE_ENTRY`], unless the module lacks it and has exactly one
+    /// compute entry point, like `main` of a GLSL compute shader.
+    pub fn compute_entry(&self) -> &str {
+        self.default_entry(naga::ShaderStage::Compute, Self::COMPUTE_ENTRY)
+    }
+
+    fn default_entry<'s>(&'s self, stage: naga::ShaderStage, name: &'s str) -> &'s str {
+        let mut entry_points = self.entry_points(stage);
+        match (entry_points.next(), entry_points.next()) {
+            (Some(only), None) if only != name => only,
+            _ => name,
+        }
+    }
+
+    /// Names of all entry points of the given stage
+    ///
+    /// Empty if the shader was created from a raw [`wgpu::ShaderModule`].
+    pub fn entry_points(&self, stage: naga::ShaderStage) -> impl Iterator<Item = &str> {
+        self.reflection
+            .iter()
+            .flat_map(|reflection| &reflection.entry_points)
+            .filter(move |ep| ep.stage == stage)
+            .map(|ep| ep.name.as_str())
+    }
+
+    /// Makes sure the module has an entry point `name` of the given stage
+    ///
+    /// Shaders created from a raw [`wgpu::ShaderModule`] can't be checked and always pass.
+    pub fn check_entry_point(
+        &self,
+        name: &str,
+        stage: naga::ShaderStage,
+    ) -> Result<(), ShaderError> {
+        let Some(reflection) = &self.reflection else {
+            return Ok(());
+        };
+
+        match reflection.entry_point(name) {
+            Some(ep) if ep.stage == stage => Ok(()),
+            Some(ep) => Err(ShaderError::WrongStage {
+                label: self.label.clone(),
+                name: name.to_string(),
+                expected: stage,
+                found: ep.stage,
+            }),
+            None => Err(ShaderError::MissingEntryPoint {
+                label: self.label.clone(),
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    fn parse_wgsl(source: &str, label: Option<&str>) -> Result<naga::Module, ShaderError> {
+        naga::front::wgsl::parse_str(source).map_err(|err| {
+            ShaderError::Parse(Box::new(ShaderDiagnostic::new(
+                label,
+                err.message(),
+                Some(source),
+                err.labels()
+                    .map(|(span, text)| (span, text.to_string()))
+                    .collect(),
+            )))
+        })
+    }
+
+    fn parse_glsl(
+        source: &str,
+        stage: naga::ShaderStage,
+        label: Option<&str>,
+    ) -> Result<naga::Module, ShaderError> {
+        naga::front::glsl::Frontend::default()
+            .parse(&naga::front::glsl::Options::from(stage), source)
+            .map_err(|errors| {
+                let message = errors
+                    .iter()
+                    .map(|err| err.kind.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let labels = errors
+                    .iter()
+                    .map(|err| (err.meta, err.kind.to_string()))
+                    .collect();
+
+                ShaderError::Parse(Box::new(ShaderDiagnostic::new(
+                    label,
+                    message,
+                    Some(source),
+                    labels,
+                )))
+            })
+    }
+
+    fn parse_spirv(bytes: &[u8], label: Option<&str>) -> Result<naga::Module, ShaderError> {
+        naga::front::spv::parse_u8_slice(bytes, &Default::default()).map_err(|err| {
+            ShaderError::Parse(Box::new(ShaderDiagnostic::new(
+                label,
+                err.to_string(),
+                None,
+                Vec::new(),
+            )))
+        })
+    }
+
+    /// `source` is only used to locate validation errors
+    fn validate(
+        module: &naga::Module,
+        source: Option<&str>,
+        label: Option<&str>,
+    ) -> Result<ShaderReflection, ShaderError> {
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(module)
+        .map_err(|err| {
+            ShaderError::Validation(Box::new(ShaderDiagnostic::new(
+                label,
+                error_chain(err.as_inner()),
+                source,
+                err.spans().cloned().collect(),
+            )))
+        })?;
+
+        Ok(ShaderReflection::new(module, &info))
+    }
+}
+
+/// Shader language, picked by file extension
+enum Language {
+    Wgsl,
+    Glsl(naga::ShaderStage),
+    SpirV,
+}
+
+impl Language {
+    fn from_path(path: &Path) -> Result<Self, ShaderError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("wgsl") => Ok(Self::Wgsl),
+            Some("vert") => Ok(Self::Glsl(naga::ShaderStage::Vertex)),
+            Some("frag") => Ok(Self::Glsl(naga::ShaderStage::Fragment)),
+            Some("comp") => Ok(Self::Glsl(naga::ShaderStage::Compute)),
+            Some("spv") => Ok(Self::SpirV),
+            _ => Err(ShaderError::UnknownLanguage {
+                path: path.to_path_buf(),
+            }),
+        }
     }
 }
 
 impl From<wgpu::ShaderModule> for Shader {
     fn from(value: ShaderModule) -> Self {
-        Self { raw: value }
+        Self {
+            raw: value,
+            label: None,
+            reflection: None,
+        }
     }
 }
+
+/// Points the diagnostic of a naga error at the original file
+fn remap_error(mut err: ShaderError, source: &PreprocessedSource) -> ShaderError {
+    if let ShaderError::Parse(diagnostic) | ShaderError::Validation(diagnostic) = &mut err {
+        diagnostic.remap(source.source_map());
+    }
+
+    err
+}
+
+/// Joins an error and all of its sources, naga's top level errors alone are not very telling
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+
+    message
+}
@@ -0,0 +1,166 @@
+use crate::wgpu::{Preprocessor, Shader, WgpuError};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Compiles and caches shader variants, keyed by path and define set
+///
+/// Variants are requested with a list of defines, each either `NAME` or
+/// `NAME=value`. A plain `NAME` is a preprocessor define for `#ifdef`, a
+/// `NAME=value` also becomes `const NAME = value;` in the shader, see
+/// [`Preprocessor::constant`]. The order of the defines doesn't matter, `["A", "B"]` and `["B", "A"]`
+/// share one module, and so do `lit.wgsl`, `./lit.wgsl` and the same file
+/// given with the root in front.
+pub struct ShaderLibrary {
+    preprocessor: Preprocessor,
+    shaders: HashMap<VariantKey, Arc<Shader>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct VariantKey {
+    path: PathBuf,
+    defines: BTreeMap<String, String>,
+}
+
+impl VariantKey {
+    fn new(root: &Path, path: &Path, defines: &[&str]) -> Self {
+        let defines = defines
+            .iter()
+            .map(|define| match define.split_once('=') {
+                Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+                None => (define.trim().to_string(), String::new()),
+            })
+            .collect();
+
+        Self {
+            path: normalize(root, path),
+            defines,
+        }
+    }
+
+    /// E.g. `lit.wgsl [NORMAL_MAP, LIGHTS=4]`
+    fn label(&self) -> String {
+        let defines = self
+            .defines
+            .iter()
+            .map(|(name, value)| match value.is_empty() {
+                true => name.clone(),
+                false => format!("{name}={value}"),
+            })
+            .collect::<Vec<_>>();
+
+        format!("{} [{}]", self.path.display(), defines.join(", "))
+    }
+}
+
+/// Makes `path` relative to `root` and drops `.` and `name/..`, without touching the file system
+fn normalize(root: &Path, path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.strip_prefix(root).unwrap_or(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+impl ShaderLibrary {
+    /// Shader paths are relative to `root`, like includes
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_preprocessor(Preprocessor::new(root))
+    }
+
+    /// Uses `preprocessor` for every variant, its defines apply to all of them
+    pub fn with_preprocessor(preprocessor: Preprocessor) -> Self {
+        Self {
+            preprocessor,
+            shaders: HashMap::new(),
+        }
+    }
+
+    pub fn preprocessor(&self) -> &Preprocessor {
+        &self.preprocessor
+    }
+
+    /// Returns the variant of the shader at `path` with the given defines,
+    /// compiling it on first use
+    pub fn get(
+        &mut self,
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+        defines: &[&str],
+    ) -> Result<Arc<Shader>, WgpuError> {
+        let key = VariantKey::new(self.preprocessor.root(), path.as_ref(), defines);
+        if let Some(shader) = self.shaders.get(&key) {
+            return Ok(Arc::clone(shader));
+        }
+
+        let preprocessor =
+            key.defines
+                .iter()
+                .fold(
+                    self.preprocessor.clone(),
+                    |preprocessor, (name, value)| match value.is_empty() {
+                        true => preprocessor.define(name, value),
+                        false => preprocessor.constant(name, value),
+                    },
+                );
+        let source = preprocessor.process_file(&key.path)?;
+        let shader = Arc::new(Shader::from_preprocessed(
+            device,
+            &source,
+            Some(&key.label()),
+        )?);
+
+        self.shaders.insert(key, Arc::clone(&shader));
+        Ok(shader)
+    }
+
+    /// Compiles all given permutations of the shader at `path` up front
+    pub fn prewarm(
+        &mut self,
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+        permutations: &[&[&str]],
+    ) -> Result<(), WgpuError> {
+        for defines in permutations {
+            self.get(device, path.as_ref(), defines)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the variant is compiled already
+    pub fn contains(&self, path: impl AsRef<Path>, defines: &[&str]) -> bool {
+        self.shaders.contains_key(&VariantKey::new(
+            self.preprocessor.root(),
+            path.as_ref(),
+            defines,
+        ))
+    }
+
+    /// Number of compiled variants over all shaders
+    pub fn variant_count(&self) -> usize {
+        self.shaders.len()
+    }
+
+    /// Number of compiled variants of the shader at `path`
+    pub fn variant_count_of(&self, path: impl AsRef<Path>) -> usize {
+        let path = normalize(self.preprocessor.root(), path.as_ref());
+        self.shaders.keys().filter(|key| key.path == path).count()
+    }
+
+    /// Drops all cached variants, pipelines keep the modules they use alive
+    pub fn clear(&mut self) {
+        self.shaders.clear();
+    }
+}
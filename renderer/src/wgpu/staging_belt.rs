@@ -0,0 +1,393 @@
+use crate::wgpu::scope;
+use crate::wgpu::{Buffer, Texture, TextureError, WgpuError};
+use std::sync::{mpsc, Arc};
+
+/// Uploads data through a ring of mapped staging buffers, recorded into a frame's encoder
+///
+/// Writes hand out a mapped slice of a staging chunk to fill in and record a
+/// copy from it into the target. Chunks are reused once the GPU is done with
+/// them, so steady per-frame uploads stop allocating after the first frames.
+/// Every frame has to go through the same steps in this order:
+///
+/// ```no_run
+/// # use renderer::wgpu::{Buffer, StagingBelt, WgpuContext};
+/// # fn frame(ctx: &WgpuContext, belt: &mut StagingBelt, buffer: &Buffer) -> Result<(), renderer::wgpu::WgpuError> {
+/// let Some(mut frame) = ctx.begin_frame()? else {
+///     return Ok(());
+/// };
+/// let size = wgpu::BufferSize::new(16).unwrap();
+/// belt.write_buffer(ctx.device(), frame.encoder(), buffer, 0, size)?
+///     .copy_from_slice(&[0; 16]);
+/// // Unmaps the chunks, has to happen before the copies are submitted
+/// belt.finish();
+/// frame.finish();
+/// // Maps the chunks again, they are free to use once the GPU is done
+/// belt.recall();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Chunks are mapped again in the background, which makes progress whenever
+/// the device is polled, e.g. on every submission.
+pub struct StagingBelt {
+    chunk_size: wgpu::BufferAddress,
+    /// Chunks written to in the current frame
+    active: Vec<Chunk>,
+    /// Chunks submitted with the current frame, waiting for [`StagingBelt::recall`]
+    closed: Vec<Chunk>,
+    /// Mapped chunks ready for reuse
+    free: Vec<Chunk>,
+    sender: mpsc::Sender<Chunk>,
+    receiver: mpsc::Receiver<Chunk>,
+    stats: UploadStats,
+    last_frame_stats: UploadStats,
+}
+
+/// What a [`StagingBelt`] uploaded within a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UploadStats {
+    /// Bytes copied into buffers and textures, without row padding
+    pub bytes: wgpu::BufferAddress,
+    /// Number of buffer and texture writes
+    pub writes: u32,
+    /// Number of staging chunks that had to be created
+    pub chunks_created: u32,
+    /// Size of the created staging chunks
+    pub bytes_allocated: wgpu::BufferAddress,
+}
+
+/// Mapped staging memory for a texture region, returned by [`StagingBelt::write_texture`]
+///
+/// Rows are padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`] in the staging
+/// buffer, rows of block compressed formats are rows of blocks. Array layers
+/// and depth slices follow each other.
+pub struct TextureWrite<'a> {
+    view: wgpu::BufferViewMut<'a>,
+    bytes_per_row: usize,
+    row_size: usize,
+    rows: usize,
+}
+
+struct Chunk {
+    buffer: Arc<wgpu::Buffer>,
+    size: wgpu::BufferAddress,
+    /// Start of the unused part
+    offset: wgpu::BufferAddress,
+}
+
+impl StagingBelt {
+    /// Creates a belt allocating chunks of `chunk_size` bytes, larger writes get
+    /// a chunk of their own which is dropped after the frame
+    ///
+    /// The chunk size should cover a typical frame's uploads, at least a few of
+    /// its largest ones.
+    pub fn new(chunk_size: wgpu::BufferAddress) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        Self {
+            chunk_size,
+            active: Vec::new(),
+            closed: Vec::new(),
+            free: Vec::new(),
+            sender,
+            receiver,
+            stats: UploadStats::default(),
+            last_frame_stats: UploadStats::default(),
+        }
+    }
+
+    /// Maps `size` bytes to be copied into `target` at `offset` once the encoder is submitted
+    ///
+    /// The same checks as for [`Buffer::write`] apply. The returned memory is
+    /// not initialized, it should be overwritten as a whole.
+    pub fn write_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &Buffer,
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferSize,
+    ) -> Result<wgpu::BufferViewMut<'_>, WgpuError> {
+        target.check_write(offset, size.get())?;
+
+        let (index, chunk_offset) = self.allocate(device, size.get(), wgpu::MAP_ALIGNMENT)?;
+        self.stats.bytes += size.get();
+        self.stats.writes += 1;
+
+        let chunk = &self.active[index];
+        encoder.copy_buffer_to_buffer(
+            &chunk.buffer,
+            chunk_offset,
+            target.raw(),
+            offset,
+            size.get(),
+        );
+
+        Ok(chunk
+            .buffer
+            .slice(chunk_offset..chunk_offset + size.get())
+            .get_mapped_range_mut())
+    }
+
+    /// Maps staging memory for the `extent` sized region of `texture` at
+    /// `origin`, copied into the texture once the encoder is submitted
+    ///
+    /// The texture needs [`wgpu::TextureUsages::COPY_DST`] and a color format,
+    /// the region has to lie within the mip level and be a non-empty, whole
+    /// number of blocks.
+    pub fn write_texture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &Texture,
+        mip_level: u32,
+        origin: wgpu::Origin3d,
+        extent: wgpu::Extent3d,
+    ) -> Result<TextureWrite<'_>, WgpuError> {
+        let label = || texture.label().map(str::to_string);
+        let raw = &texture.raw;
+        let format = raw.format();
+
+        // Only color formats are supported, some depth formats have a block size too
+        let block_size = format
+            .block_size(None)
+            .filter(|_| format.has_color_aspect())
+            .ok_or_else(|| TextureError::UnsupportedFormat {
+                label: label(),
+                format,
+            })?;
+        if !raw.usage().contains(wgpu::TextureUsages::COPY_DST) {
+            return Err(TextureError::MissingUsage {
+                label: label(),
+                usage: wgpu::TextureUsages::COPY_DST,
+            }
+            .into());
+        }
+
+        let (block_width, block_height) = format.block_dimensions();
+        let valid = mip_level < raw.mip_level_count() && {
+            let mip_size = raw
+                .size()
+                .mip_level_size(mip_level, raw.dimension())
+                .physical_size(format);
+            let fits = |origin: u32, extent: u32, size: u32| {
+                extent > 0 && origin.checked_add(extent).is_some_and(|end| end <= size)
+            };
+
+            fits(origin.x, extent.width, mip_size.width)
+                && fits(origin.y, extent.height, mip_size.height)
+                && fits(
+                    origin.z,
+                    extent.depth_or_array_layers,
+                    mip_size.depth_or_array_layers,
+                )
+                && [origin.x, extent.width]
+                    .iter()
+                    .all(|v| v % block_width == 0)
+                && [origin.y, extent.height]
+                    .iter()
+                    .all(|v| v % block_height == 0)
+        };
+        if !valid {
+            return Err(TextureError::InvalidRegion {
+                label: label(),
+                mip_level,
+                origin,
+                extent,
+            }
+            .into());
+        }
+
+        let row_size = extent.width / block_width * block_size;
+        let rows_per_image = extent.height / block_height;
+        let bytes_per_row = row_size.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let rows = rows_per_image as wgpu::BufferAddress
+            * extent.depth_or_array_layers as wgpu::BufferAddress;
+        let size = bytes_per_row as wgpu::BufferAddress * rows;
+
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress;
+        let (index, chunk_offset) = self.allocate(device, size, alignment)?;
+        self.stats.bytes += row_size as wgpu::BufferAddress * rows;
+        self.stats.writes += 1;
+
+        let chunk = &self.active[index];
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &chunk.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: chunk_offset,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(rows_per_image),
+                },
+            },
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: raw,
+                mip_level,
+                origin,
+            },
+            extent,
+        );
+
+        Ok(TextureWrite {
+            view: chunk
+                .buffer
+                .slice(chunk_offset..chunk_offset + size)
+                .get_mapped_range_mut(),
+            bytes_per_row: bytes_per_row as usize,
+            row_size: row_size as usize,
+            rows: rows as usize,
+        })
+    }
+
+    /// Unmaps the chunks written to this frame, call it before submitting the encoder
+    pub fn finish(&mut self) {
+        for chunk in self.active.drain(..) {
+            chunk.buffer.unmap();
+            self.closed.push(chunk);
+        }
+    }
+
+    /// Starts mapping the submitted chunks again and ends the frame's statistics,
+    /// call it after submitting the encoder
+    pub fn recall(&mut self) {
+        self.receive_chunks();
+
+        for mut chunk in self.closed.drain(..) {
+            // Chunks made for a single large write would sit in `free` forever
+            if chunk.size > self.chunk_size {
+                continue;
+            }
+            let sender = self.sender.clone();
+            let buffer = chunk.buffer.clone();
+            chunk.offset = 0;
+
+            buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Write, move |result| {
+                    // Chunks that fail to map are dropped, the receiver goes away with the belt
+                    if result.is_ok() {
+                        let _ = sender.send(chunk);
+                    }
+                });
+        }
+
+        self.last_frame_stats = std::mem::take(&mut self.stats);
+    }
+
+    /// Uploads of the current frame so far
+    pub fn stats(&self) -> UploadStats {
+        self.stats
+    }
+
+    /// Uploads of the frame ended by the last [`StagingBelt::recall`]
+    pub fn last_frame_stats(&self) -> UploadStats {
+        self.last_frame_stats
+    }
+
+    /// Finds room for `size` bytes at an `alignment` multiple in a mapped chunk,
+    /// returns the index of the active chunk and the offset in it
+    fn allocate(
+        &mut self,
+        device: &wgpu::Device,
+        size: wgpu::BufferAddress,
+        alignment: wgpu::BufferAddress,
+    ) -> Result<(usize, wgpu::BufferAddress), WgpuError> {
+        let fits = |chunk: &Chunk| align_up(chunk.offset, alignment) + size <= chunk.size;
+
+        let index = match self.active.iter().position(fits) {
+            Some(index) => index,
+            None => {
+                self.receive_chunks();
+                let chunk = match self.free.iter().position(fits) {
+                    Some(index) => self.free.swap_remove(index),
+                    None => self.create_chunk(device, size)?,
+                };
+                self.active.push(chunk);
+                self.active.len() - 1
+            }
+        };
+
+        let chunk = &mut self.active[index];
+        let offset = align_up(chunk.offset, alignment);
+        chunk.offset = offset + size;
+
+        Ok((index, offset))
+    }
+
+    fn create_chunk(
+        &mut self,
+        device: &wgpu::Device,
+        size: wgpu::BufferAddress,
+    ) -> Result<Chunk, WgpuError> {
+        let size = self.chunk_size.max(size);
+        let label = Some("staging belt chunk");
+        let buffer = scope::capture(device, label, || {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label,
+                size,
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            })
+        })?;
+
+        self.stats.chunks_created += 1;
+        self.stats.bytes_allocated += size;
+
+        Ok(Chunk {
+            buffer: Arc::new(buffer),
+            size,
+            offset: 0,
+        })
+    }
+
+    /// Moves the chunks that got mapped again to the free list
+    fn receive_chunks(&mut self) {
+        self.free.extend(self.receiver.try_iter());
+    }
+}
+
+impl<'a> TextureWrite<'a> {
+    /// Number of rows, over all array layers or depth slices
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Size of a row without padding
+    pub fn row_size(&self) -> usize {
+        self.row_size
+    }
+
+    /// The row at `index`, without padding
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than [`TextureWrite::rows`].
+    pub fn row_mut(&mut self, index: usize) -> &mut [u8] {
+        assert!(index < self.rows, "row {index} out of {}", self.rows);
+        let start = index * self.bytes_per_row;
+        &mut self.view[start..start + self.row_size]
+    }
+
+    /// Copies tightly packed rows into the padded staging memory
+    ///
+    /// # Panics
+    ///
+    /// If `data` doesn't hold exactly [`TextureWrite::rows`] rows of
+    /// [`TextureWrite::row_size`] bytes.
+    pub fn copy_from_slice(&mut self, data: &[u8]) {
+        assert_eq!(
+            data.len(),
+            self.rows * self.row_size,
+            "texture data has the wrong size"
+        );
+        for (index, row) in data.chunks_exact(self.row_size).enumerate() {
+            self.row_mut(index).copy_from_slice(row);
+        }
+    }
+}
+
+fn align_up(offset: wgpu::BufferAddress, alignment: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    offset.div_ceil(alignment) * alignment
+}
@@ -1,24 +1,80 @@
+use crate::wgpu::scope;
+use crate::wgpu::{BufferError, TextureError, WgpuError};
 use image::GenericImageView;
-use std::error::Error;
+use std::path::Path;
 
 #[derive(Debug)]
 pub struct Texture {
     pub raw: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    label: Option<String>,
 }
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+    /// Creates a texture from a descriptor, for formats and usages the other
+    /// constructors don't cover, e.g. a [`crate::wgpu::StagingBelt`] target
+    pub fn new(
+        device: &wgpu::Device,
+        desc: &wgpu::TextureDescriptor<'_>,
+    ) -> Result<Self, WgpuError> {
+        let raw = scope::capture(device, desc.label, || device.create_texture(desc))?;
+        let view = raw.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            raw,
+            view,
+            sampler,
+            label: desc.label.map(str::to_string),
+        })
+    }
+
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: impl AsRef<[u8]>,
         label: Option<&str>,
-    ) -> Result<Self, Box<dyn Error>> {
-        let img = image::load_from_memory(bytes.as_ref())?;
-        Ok(Self::from_image(device, queue, &img, label))
+    ) -> Result<Self, WgpuError> {
+        let img =
+            image::load_from_memory(bytes.as_ref()).map_err(|source| TextureError::Decode {
+                label: label.map(str::to_string),
+                path: None,
+                source,
+            })?;
+        Self::from_image(device, queue, &img, label)
+    }
+
+    /// Loads and decodes an image file, the path is used as label
+    pub fn from_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, WgpuError> {
+        let path = path.as_ref();
+        let label = path.display().to_string();
+
+        let bytes = std::fs::read(path).map_err(|source| TextureError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let img = image::load_from_memory(&bytes).map_err(|source| TextureError::Decode {
+            label: Some(label.clone()),
+            path: Some(path.to_path_buf()),
+            source,
+        })?;
+
+        Self::from_image(device, queue, &img, Some(&label))
     }
 
     pub fn from_image(
@@ -26,7 +82,7 @@ impl Texture {
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
-    ) -> Self {
+    ) -> Result<Self, WgpuError> {
         let dimensions = img.dimensions();
         let rgba = img.to_rgba8();
 
@@ -36,32 +92,36 @@ impl Texture {
             depth_or_array_layers: 1,
         };
 
-        let raw = device.create_texture(&wgpu::TextureDescriptor {
-            label,
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
-        });
+        let raw = scope::capture(device, label, || {
+            let raw = device.create_texture(&wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+            });
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                aspect: wgpu::TextureAspect::All,
-                texture: &raw,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &raw,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * dimensions.0),
+                    rows_per_image: Some(dimensions.1),
+                },
+                size,
+            );
+
+            raw
+        })?;
 
         let view = raw.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -74,17 +134,23 @@ impl Texture {
             ..Default::default()
         });
 
-        Self { raw, view, sampler }
+        Ok(Self {
+            raw,
+            view,
+            sampler,
+            label: label.map(str::to_string),
+        })
     }
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
+        width: u32,
+        height: u32,
         label: Option<&str>,
-    ) -> Self {
+    ) -> Result<Self, WgpuError> {
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
@@ -99,7 +165,7 @@ impl Texture {
             view_formats: &[],
         };
 
-        let raw = device.create_texture(&desc);
+        let raw = scope::capture(device, label, || device.create_texture(&desc))?;
         let view = raw.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -114,6 +180,254 @@ impl Texture {
             ..Default::default()
         });
 
-        Self { raw, view, sampler }
+        Ok(Self {
+            raw,
+            view,
+            sampler,
+            label: label.map(str::to_string),
+        })
+    }
+
+    /// Creates a color texture that can be rendered into and copied from,
+    /// used as the target of a headless context
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let raw = scope::capture(device, label, || {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        })?;
+
+        let view = raw.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            raw,
+            view,
+            sampler,
+            label: label.map(str::to_string),
+        })
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Copies the texture back to the cpu, blocking until the gpu is done.
+    ///
+    /// See [`Texture::read_texture_to_image`]
+    pub fn read_to_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<image::RgbaImage, WgpuError> {
+        Self::read_labeled_texture_to_image(device, queue, &self.raw, self.label())
+    }
+
+    /// Copies the first mip level of a 2D texture into an [`image::RgbaImage`],
+    /// blocking until the gpu is done.
+    ///
+    /// The texture needs [`wgpu::TextureUsages::COPY_SRC`]. The image always
+    /// holds sRGB encoded colors: sRGB formats are copied as they are stored,
+    /// all other formats are treated as linear and get sRGB encoded. Alpha
+    /// stays linear.
+    pub fn read_texture_to_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+    ) -> Result<image::RgbaImage, WgpuError> {
+        Self::read_labeled_texture_to_image(device, queue, texture, None)
+    }
+
+    fn read_labeled_texture_to_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        label: Option<&str>,
+    ) -> Result<image::RgbaImage, WgpuError> {
+        let format = texture.format();
+        let bytes_per_pixel = match format {
+            wgpu::TextureFormat::Rgba8Unorm
+            | wgpu::TextureFormat::Rgba8UnormSrgb
+            | wgpu::TextureFormat::Bgra8Unorm
+            | wgpu::TextureFormat::Bgra8UnormSrgb => 4,
+            wgpu::TextureFormat::Rgba16Float => 8,
+            wgpu::TextureFormat::Rgba32Float => 16,
+            _ => {
+                return Err(TextureError::UnsupportedFormat {
+                    label: label.map(str::to_string),
+                    format,
+                }
+                .into())
+            }
+        };
+
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(TextureError::MissingUsage {
+                label: label.map(str::to_string),
+                usage: wgpu::TextureUsages::COPY_SRC,
+            }
+            .into());
+        }
+
+        let (width, height) = (texture.width(), texture.height());
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("texture readback buffer"),
+            size: padded_bytes_per_row as wgpu::BufferAddress * height as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture readback encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            // Receiver only goes away if this function returned already
+            let _ = sender.send(res);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .unwrap_or(Err(wgpu::BufferAsyncError))
+            .map_err(|source| BufferError::Map {
+                label: Some(String::from("texture readback buffer")),
+                source,
+            })?;
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        {
+            let mapped = slice.get_mapped_range();
+            for row in mapped.chunks_exact(padded_bytes_per_row as usize) {
+                let row = &row[..unpadded_bytes_per_row as usize];
+                match format {
+                    wgpu::TextureFormat::Rgba8UnormSrgb => pixels.extend_from_slice(row),
+                    wgpu::TextureFormat::Bgra8UnormSrgb => {
+                        for bgra in row.chunks_exact(4) {
+                            pixels.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+                        }
+                    }
+                    wgpu::TextureFormat::Rgba8Unorm => {
+                        for (i, &c) in row.iter().enumerate() {
+                            pixels.push(linear_to_srgb_byte(c as f32 / 255.0, i % 4 == 3));
+                        }
+                    }
+                    wgpu::TextureFormat::Bgra8Unorm => {
+                        for bgra in row.chunks_exact(4) {
+                            for (i, &c) in [bgra[2], bgra[1], bgra[0], bgra[3]].iter().enumerate() {
+                                pixels.push(linear_to_srgb_byte(c as f32 / 255.0, i == 3));
+                            }
+                        }
+                    }
+                    wgpu::TextureFormat::Rgba16Float => {
+                        for channels in row.chunks_exact(8) {
+                            for (i, c) in channels.chunks_exact(2).enumerate() {
+                                let value = f16_to_f32(u16::from_le_bytes([c[0], c[1]]));
+                                pixels.push(linear_to_srgb_byte(value, i == 3));
+                            }
+                        }
+                    }
+                    wgpu::TextureFormat::Rgba32Float => {
+                        for channels in row.chunks_exact(16) {
+                            for (i, c) in channels.chunks_exact(4).enumerate() {
+                                let value = f32::from_le_bytes([c[0], c[1], c[2], c[3]]);
+                                pixels.push(linear_to_srgb_byte(value, i == 3));
+                            }
+                        }
+                    }
+                    // Can't crash, other formats were rejected above
+                    _ => unreachable!(),
+                }
+            }
+        }
+        staging.unmap();
+
+        // Can't fail, since the pixel buffer has exactly width * height * 4 bytes
+        Ok(image::RgbaImage::from_raw(width, height, pixels).unwrap_or_else(|| unreachable!()))
+    }
+}
+
+/// Converts a linear channel value to an 8-bit sRGB value, alpha stays linear
+fn linear_to_srgb_byte(value: f32, is_alpha: bool) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if is_alpha {
+        value
+    } else if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * 255.0).round() as u8
+}
+
+/// Decodes an IEEE 754 half precision float
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
     }
 }
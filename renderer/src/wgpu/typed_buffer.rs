@@ -0,0 +1,120 @@
+use crate::wgpu::{Buffer, BufferError, WgpuContext, WgpuError};
+use std::marker::PhantomData;
+
+/// A [`Buffer`] holding a fixed number of `T`s, written element-wise
+///
+/// Writes go through [`Buffer::write`], so the same usage, bounds and
+/// alignment checks apply. E.g. writes of `u16` indices have to cover an even
+/// number of elements starting at an even index.
+pub struct TypedBuffer<T: bytemuck::Pod> {
+    buffer: Buffer,
+    len: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> TypedBuffer<T> {
+    const ELEMENT_SIZE: wgpu::BufferAddress = std::mem::size_of::<T>() as wgpu::BufferAddress;
+
+    /// Creates a zeroed buffer with room for `len` elements
+    pub fn new(
+        device: &wgpu::Device,
+        len: u64,
+        usages: wgpu::BufferUsages,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let size = len
+            .checked_mul(Self::ELEMENT_SIZE)
+            .ok_or_else(|| BufferError::TooLarge {
+                label: label.map(str::to_string),
+                len,
+                element_size: Self::ELEMENT_SIZE,
+            })?;
+        let buffer = Buffer::new(device, size, usages, false, label)?;
+
+        Ok(Self {
+            buffer,
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a buffer holding `data`
+    pub fn new_init(
+        device: &wgpu::Device,
+        data: &[T],
+        usages: wgpu::BufferUsages,
+        label: Option<&str>,
+    ) -> Result<Self, WgpuError> {
+        let buffer = Buffer::new_init(device, bytemuck::cast_slice(data), usages, label)?;
+
+        Ok(Self {
+            buffer,
+            len: data.len() as u64,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size in bytes
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.buffer.size()
+    }
+
+    pub fn usages(&self) -> wgpu::BufferUsages {
+        self.buffer.usages()
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn raw(&self) -> &wgpu::Buffer {
+        self.buffer.raw()
+    }
+
+    /// Schedules writing `data` starting at element `index`
+    pub fn write(&self, queue: &wgpu::Queue, index: u64, data: &[T]) -> Result<(), WgpuError> {
+        let offset = index
+            .checked_mul(Self::ELEMENT_SIZE)
+            .unwrap_or(wgpu::BufferAddress::MAX);
+
+        self.buffer.write(queue, offset, bytemuck::cast_slice(data))
+    }
+
+    /// Schedules replacing all elements, `data` has to be exactly [`TypedBuffer::len`] long
+    pub fn write_all(&self, queue: &wgpu::Queue, data: &[T]) -> Result<(), WgpuError> {
+        if data.len() as u64 != self.len {
+            return Err(BufferError::LengthMismatch {
+                label: self.buffer.label().map(str::to_string),
+                expected: self.len,
+                found: data.len() as u64,
+            }
+            .into());
+        }
+
+        self.write(queue, 0, data)
+    }
+
+    /// Copies the elements back to the CPU, see [`Buffer::read_async`]
+    pub async fn read_async(&self, ctx: &WgpuContext) -> Result<Vec<T>, WgpuError> {
+        let mut data = self.buffer.read_async(ctx).await?;
+        // The buffer can be padded to whole words
+        data.truncate(self.len as usize);
+        Ok(data)
+    }
+
+    /// Like [`TypedBuffer::read_async`], blocking until the data is there
+    pub fn read(&self, ctx: &WgpuContext) -> Result<Vec<T>, WgpuError> {
+        let mut data = self.buffer.read(ctx)?;
+        data.truncate(self.len as usize);
+        Ok(data)
+    }
+}
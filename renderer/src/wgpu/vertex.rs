@@ -1,3 +1,43 @@
+/// Describes the buffer layout of a vertex type
+///
+/// Usually derived with `#[derive(Vertex)]`, see [`renderer_derive::Vertex`].
+///
+/// The derive only accepts `#[repr(C)]` structs
+///
+/// ```compile_fail
+/// use renderer::wgpu::Vertex;
+///
+/// #[repr(transparent)]
+/// #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+/// struct V {
+///     position: [f32; 3],
+/// }
+/// ```
+///
+/// that implement `bytemuck::Pod`
+///
+/// ```compile_fail,E0277
+/// use renderer::wgpu::Vertex;
+///
+/// #[repr(C)]
+/// #[derive(Clone, Copy, Vertex)]
+/// struct V {
+///     position: [f32; 3],
+/// }
+/// ```
+///
+/// and whose fields have a vertex format.
+///
+/// ```compile_fail
+/// use renderer::wgpu::Vertex;
+///
+/// #[repr(C)]
+/// #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+/// struct V {
+///     position: [f32; 2],
+///     id: u64,
+/// }
+/// ```
 pub trait Vertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
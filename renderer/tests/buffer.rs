@@ -0,0 +1,263 @@
+mod common;
+
+use renderer::wgpu::{Buffer, BufferError, GpuVec, TypedBuffer, WgpuError};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+/// Counts how often the future asked to be polled again
+#[derive(Default)]
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn typed_buffer_size_overflow_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+
+    let result = TypedBuffer::<[u32; 4]>::new(
+        ctx.device(),
+        u64::MAX / 8,
+        wgpu::BufferUsages::STORAGE,
+        Some("huge"),
+    );
+
+    match result {
+        Err(WgpuError::Buffer(BufferError::TooLarge {
+            len, element_size, ..
+        })) => assert_eq!((len, element_size), (u64::MAX / 8, 16)),
+        Err(e) => panic!("expected the size to overflow, got {e}"),
+        Ok(_) => panic!("expected the size to overflow, the buffer was created"),
+    }
+}
+
+#[test]
+fn typed_buffer_writes_are_bounds_checked() {
+    let Some(ctx) = common::context() else { return };
+
+    let buffer = TypedBuffer::<u32>::new(
+        ctx.device(),
+        4,
+        wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        Some("bounds"),
+    )
+    .unwrap();
+
+    match buffer.write(ctx.queue(), 2, &[1, 2, 3]) {
+        Err(WgpuError::Buffer(BufferError::OutOfBounds {
+            offset,
+            size,
+            buffer_size,
+            ..
+        })) => assert_eq!((offset, size, buffer_size), (8, 12, 16)),
+        Err(e) => panic!("expected an out of bounds write, got {e}"),
+        Ok(()) => panic!("expected an out of bounds write, it was scheduled"),
+    }
+    // The byte offset of the index overflows
+    assert!(matches!(
+        buffer.write(ctx.queue(), u64::MAX / 2, &[1]),
+        Err(WgpuError::Buffer(BufferError::OutOfBounds { .. }))
+    ));
+
+    buffer.write(ctx.queue(), 2, &[7, 8]).unwrap();
+    assert_eq!(buffer.read(&ctx).unwrap(), [0, 0, 7, 8]);
+}
+
+#[test]
+fn typed_buffer_write_needs_copy_dst() {
+    let Some(ctx) = common::context() else { return };
+
+    let buffer = TypedBuffer::<u32>::new(
+        ctx.device(),
+        4,
+        wgpu::BufferUsages::STORAGE,
+        Some("no copy"),
+    )
+    .unwrap();
+
+    match buffer.write(ctx.queue(), 0, &[1]) {
+        Err(WgpuError::Buffer(BufferError::MissingUsage { label, usage })) => {
+            assert_eq!(label.as_deref(), Some("no copy"));
+            assert_eq!(usage, wgpu::BufferUsages::COPY_DST);
+        }
+        Err(e) => panic!("expected a missing usage, got {e}"),
+        Ok(()) => panic!("expected a missing usage, the write was scheduled"),
+    }
+}
+
+#[test]
+fn typed_buffer_write_all_needs_every_element() {
+    let Some(ctx) = common::context() else { return };
+
+    let buffer = TypedBuffer::<u32>::new(
+        ctx.device(),
+        4,
+        wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        Some("all"),
+    )
+    .unwrap();
+
+    for data in [&[1, 2, 3][..], &[1, 2, 3, 4, 5]] {
+        match buffer.write_all(ctx.queue(), data) {
+            Err(WgpuError::Buffer(BufferError::LengthMismatch {
+                expected, found, ..
+            })) => assert_eq!((expected, found), (4, data.len() as u64)),
+            Err(e) => panic!("expected a length mismatch, got {e}"),
+            Ok(()) => panic!("expected a length mismatch, the write was scheduled"),
+        }
+    }
+
+    buffer.write_all(ctx.queue(), &[1, 2, 3, 4]).unwrap();
+    assert_eq!(buffer.read(&ctx).unwrap(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn gpu_vec_size_overflow_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+
+    let result = GpuVec::<[u32; 4]>::with_capacity(
+        &ctx,
+        usize::MAX / 8,
+        wgpu::BufferUsages::STORAGE,
+        Some("huge"),
+    );
+
+    assert!(matches!(
+        result,
+        Err(WgpuError::Buffer(BufferError::TooLarge { .. }))
+    ));
+}
+
+#[test]
+fn gpu_vec_keeps_its_contents_while_growing() {
+    let Some(ctx) = common::context() else { return };
+
+    // Six bytes per element, so elements straddle the words that copies work in
+    let mut vec = GpuVec::<[u16; 3]>::new(&ctx, wgpu::BufferUsages::STORAGE, Some("grow")).unwrap();
+    let element = |i: u16| [3 * i + 1, 3 * i + 2, 3 * i + 3];
+
+    // (elements pushed before the sync, whether it grows, capacity after)
+    let steps = [
+        (1, false, 1),
+        (1, true, 2),
+        (1, true, 4),
+        (1, false, 4),
+        (5, true, 9),
+        (0, false, 9),
+        (2, true, 18),
+    ];
+    let mut len = 0;
+    for (pushed, grows, capacity) in steps {
+        vec.extend((len..len + pushed).map(element));
+        len += pushed;
+
+        assert_eq!(vec.sync(&ctx).unwrap(), grows, "syncing {len} elements");
+        assert_eq!(vec.capacity(), capacity);
+
+        let data = vec.buffer().read::<[u16; 3]>(&ctx).unwrap();
+        assert_eq!(data.len(), capacity);
+        assert_eq!(data[..len as usize], *vec.as_slice());
+        assert_eq!(vec.as_slice(), (0..len).map(element).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn buffers_of_partial_words_can_be_read() {
+    let Some(ctx) = common::context() else { return };
+
+    let bytes: Vec<u8> = (1..=12).collect();
+    let source = Buffer::new(
+        ctx.device(),
+        10,
+        wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        false,
+        Some("partial words"),
+    )
+    .unwrap();
+    assert_eq!(source.size(), 12);
+    source.write(ctx.queue(), 0, &bytes).unwrap();
+
+    // Copied to a staging buffer
+    let expected = [[1, 2, 3, 4, 5], [6, 7, 8, 9, 10]];
+    assert_eq!(source.read::<[u8; 5]>(&ctx).unwrap(), expected);
+
+    // Mapped directly
+    let mappable = Buffer::new(
+        ctx.device(),
+        10,
+        wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        false,
+        Some("mappable partial words"),
+    )
+    .unwrap();
+    let mut encoder = ctx
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_buffer_to_buffer(source.raw(), 0, mappable.raw(), 0, 12);
+    ctx.queue().submit([encoder.finish()]);
+    assert_eq!(mappable.read::<[u8; 5]>(&ctx).unwrap(), expected);
+}
+
+#[test]
+fn read_async_completes_under_a_plain_executor() {
+    let Some(ctx) = common::context() else { return };
+
+    let values: Vec<u32> = (0..1024).collect();
+    let buffer = TypedBuffer::new_init(
+        ctx.device(),
+        &values,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        Some("read async"),
+    )
+    .unwrap();
+
+    // beul only polls again once woken, nothing else polls the device here
+    let data = beul::execute(buffer.read_async(&ctx)).unwrap();
+    assert_eq!(data, values);
+}
+
+#[test]
+fn read_async_waits_instead_of_spinning() {
+    let Some(ctx) = common::context() else { return };
+
+    let values: Vec<u32> = (0..1024).collect();
+    let buffer = TypedBuffer::new_init(
+        ctx.device(),
+        &values,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        Some("read async polls"),
+    )
+    .unwrap();
+
+    let wakes = Arc::new(CountingWaker::default());
+    let waker = Waker::from(Arc::clone(&wakes));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(buffer.read_async(&ctx));
+
+    // The first poll doesn't block, the second one waits for the GPU
+    for poll in 1.. {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(data) => {
+                assert_eq!(data.unwrap(), values);
+                break;
+            }
+            Poll::Pending => {
+                assert!(poll < 2, "still pending after waiting for the GPU");
+                assert_eq!(
+                    wakes.0.load(Ordering::SeqCst),
+                    poll,
+                    "pending without a wake"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// Each test file only uses some of the helpers
+#![allow(dead_code)]
+
+use renderer::wgpu::{WgpuContext, WgpuContextBuilder, WgpuError};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The GL backend of the software adapter can't create a second instance
+/// while one is alive, so tests take turns
+static GPU: Mutex<()> = Mutex::new(());
+
+/// A context that keeps other tests from creating one until it's dropped
+pub struct TestContext {
+    ctx: WgpuContext,
+    _gpu: MutexGuard<'static, ()>,
+}
+
+impl Deref for TestContext {
+    type Target = WgpuContext;
+
+    fn deref(&self) -> &WgpuContext {
+        &self.ctx
+    }
+}
+
+/// Creates a small headless context on a software adapter, or `None` if this
+/// machine has no software adapter
+pub fn context() -> Option<TestContext> {
+    build(builder())
+        .map(|result| result.unwrap_or_else(|e| panic!("failed to create context: {e}")))
+}
+
+/// A builder picking the software adapter
+pub fn builder() -> WgpuContextBuilder {
+    WgpuContextBuilder::new().force_fallback_adapter(true)
+}
+
+/// Builds a small headless context, `None` if this machine has no software adapter
+///
+/// Blocks while another test holds a context.
+pub fn build(builder: WgpuContextBuilder) -> Option<Result<TestContext, WgpuError>> {
+    let gpu = GPU.lock().unwrap_or_else(PoisonError::into_inner);
+    match beul::execute(builder.build_headless(16, 16)) {
+        Ok(ctx) => Some(Ok(TestContext { ctx, _gpu: gpu })),
+        Err(WgpuError::NoFittingAdapterFound) => {
+            eprintln!("no software adapter available, skipping test");
+            None
+        }
+        Err(e) => Some(Err(e)),
+    }
+}
+
+/// An empty directory below cargo's temporary directory for the test's files
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    // Leftovers of an earlier run
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
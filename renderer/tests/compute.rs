@@ -0,0 +1,95 @@
+mod common;
+
+use renderer::wgpu::{ComputePipeline, PipelineError, ShaderSource, TypedBuffer, WgpuError};
+
+const NOOP: &str = r"
+@compute @workgroup_size(1)
+fn cs_main() {}
+";
+
+const WIDTH: u32 = 13;
+const HEIGHT: u32 = 7;
+
+/// Counts the invocations of each cell of a 13x7 grid, the last element counts all of them
+const COUNT: &str = r"
+const WIDTH = 13u;
+const HEIGHT = 7u;
+
+@group(0) @binding(0)
+var<storage, read_write> counts: array<atomic<u32>>;
+
+@compute @workgroup_size(8, 4, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    atomicAdd(&counts[WIDTH * HEIGHT], 1u);
+    if id.x < WIDTH && id.y < HEIGHT {
+        atomicAdd(&counts[id.y * WIDTH + id.x], 1u);
+    }
+}
+";
+
+#[test]
+fn zero_workgroup_size_is_rejected() {
+    let Some(ctx) = common::context() else { return };
+
+    // A raw module has no reflection, so the size given to the builder is used
+    let module = ctx
+        .device()
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(NOOP.into()),
+        });
+    let result = ComputePipeline::builder()
+        .label("zero workgroup")
+        .entry_point("cs_main")
+        .workgroup_size([64, 0, 1])
+        .build(&ctx, ShaderSource::Module(module));
+
+    match result {
+        Err(WgpuError::Pipeline(PipelineError::InvalidWorkgroupSize { size, .. })) => {
+            assert_eq!(size, [64, 0, 1])
+        }
+        Err(e) => panic!("expected an invalid workgroup size, got {e}"),
+        Ok(_) => panic!("expected an invalid workgroup size, the pipeline was built"),
+    }
+}
+
+#[test]
+fn dispatch_covers_every_cell_once() {
+    let Some(ctx) = common::context() else { return };
+
+    let pipeline = ComputePipeline::builder()
+        .workgroup_size([0, 0, 0])
+        .build(&ctx, ShaderSource::SourceCode(COUNT))
+        .unwrap();
+    assert_eq!(pipeline.workgroup_size(), [8, 4, 1]);
+    assert_eq!(pipeline.workgroup_count([WIDTH, HEIGHT, 1]), [2, 2, 1]);
+
+    let cells = (WIDTH * HEIGHT) as usize;
+    let counts = TypedBuffer::<u32>::new(
+        ctx.device(),
+        cells as u64 + 1,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        Some("invocation counts"),
+    )
+    .unwrap();
+    let bind_group = ctx.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: pipeline.bind_group_layout(0).unwrap(),
+        entries: &[counts.buffer().bind_group_entry(0)],
+    });
+
+    let mut encoder = ctx
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        pass.set_bind_group(0, &bind_group, &[]);
+        pipeline.dispatch_2d(&mut pass, WIDTH, HEIGHT);
+    }
+    ctx.queue().submit([encoder.finish()]);
+
+    let counts = counts.read(&ctx).unwrap();
+    assert_eq!(counts[..cells], vec![1; cells]);
+    // Two by two workgroups of 8x4, the last ones partly out of bounds
+    assert_eq!(counts[cells], 4 * 8 * 4);
+}
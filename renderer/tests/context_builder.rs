@@ -0,0 +1,68 @@
+mod common;
+
+use renderer::wgpu::WgpuError;
+
+/// Features of the software adapter, and one it lacks if there is one
+///
+/// Only one context can exist at a time, so this one is gone before the test builds its own.
+fn adapter_features() -> Option<(wgpu::Features, Option<wgpu::Features>)> {
+    let ctx = common::context()?;
+    let supported = ctx.adapter().features();
+    let unsupported = wgpu::Features::all()
+        .iter()
+        .find(|feature| !supported.contains(*feature));
+
+    Some((supported, unsupported))
+}
+
+#[test]
+fn optional_features_are_granted_if_supported() {
+    let Some((supported, Some(unsupported))) = adapter_features() else {
+        return;
+    };
+
+    let ctx = common::build(common::builder().optional_features(supported | unsupported))
+        .unwrap()
+        .unwrap();
+
+    assert!(ctx.features().contains(supported));
+    assert!(!ctx.features().contains(unsupported));
+}
+
+#[test]
+fn missing_required_features_are_an_error() {
+    let Some((_, Some(unsupported))) = adapter_features() else {
+        return;
+    };
+
+    match common::build(common::builder().features(unsupported)).unwrap() {
+        Err(WgpuError::UnsupportedFeatures(missing)) => assert_eq!(missing, unsupported),
+        Err(e) => panic!("expected unsupported features, got {e}"),
+        Ok(_) => panic!("expected unsupported features, the context was created"),
+    }
+}
+
+#[test]
+fn exceeding_limits_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+    let allowed = ctx.adapter().limits().max_texture_dimension_2d;
+    drop(ctx);
+
+    let limits = wgpu::Limits {
+        max_texture_dimension_2d: allowed + 1,
+        ..wgpu::Limits::downlevel_defaults()
+    };
+    match common::build(common::builder().limits(limits)).unwrap() {
+        Err(WgpuError::UnsupportedLimit {
+            name,
+            requested,
+            allowed: reported,
+        }) => {
+            assert_eq!(name, "max_texture_dimension_2d");
+            assert_eq!(requested, u64::from(allowed) + 1);
+            assert_eq!(reported, u64::from(allowed));
+        }
+        Err(e) => panic!("expected an unsupported limit, got {e}"),
+        Ok(_) => panic!("expected an unsupported limit, the context was created"),
+    }
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use renderer::wgpu::{Buffer, WgpuError};
+use std::sync::{Arc, Mutex};
+
+/// Mapping for reading and writing at once is invalid without a native-only feature
+const INVALID_USAGES: wgpu::BufferUsages =
+    wgpu::BufferUsages::MAP_READ.union(wgpu::BufferUsages::MAP_WRITE);
+
+#[test]
+fn invalid_descriptors_are_validation_errors() {
+    let Some(ctx) = common::context() else { return };
+
+    let result = Buffer::new(ctx.device(), 16, INVALID_USAGES, false, Some("invalid"));
+
+    match result {
+        Err(WgpuError::Validation { label, description }) => {
+            assert_eq!(label.as_deref(), Some("invalid"));
+            assert!(!description.is_empty());
+        }
+        Err(e) => panic!("expected a validation error, got {e}"),
+        Ok(_) => panic!("expected a validation error, the buffer was created"),
+    }
+}
+
+#[test]
+fn uncaptured_errors_reach_the_callback() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let callback_errors = Arc::clone(&errors);
+    let builder = common::builder().on_uncaptured_error(move |err| {
+        callback_errors.lock().unwrap().push(err.to_string());
+    });
+    let Some(ctx) = common::build(builder) else {
+        return;
+    };
+    let ctx = ctx.unwrap();
+
+    // Outside of an error scope, unlike the renderer's constructors
+    ctx.device().create_buffer(&wgpu::BufferDescriptor {
+        label: Some("uncaptured"),
+        size: 16,
+        usage: INVALID_USAGES,
+        mapped_at_creation: false,
+    });
+
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("uncaptured"), "{}", errors[0]);
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use renderer::wgpu::{RenderPipeline, ShaderSource};
+
+const FULLSCREEN_RED: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+"#;
+
+#[test]
+fn pipeline_without_depth_draws_in_pass_without_depth() {
+    let Some(ctx) = common::context() else { return };
+
+    let pipeline = RenderPipeline::builder()
+        .label("no depth pipeline")
+        .depth_stencil(None)
+        .build(&ctx, ShaderSource::SourceCode(FULLSCREEN_RED))
+        .unwrap();
+
+    ctx.device().push_error_scope(wgpu::ErrorFilter::Validation);
+    let mut frame = ctx.begin_frame().unwrap().unwrap();
+    {
+        let mut pass = frame.begin_render_pass_with(None, Some(wgpu::Color::BLACK), None);
+        pass.set_pipeline(pipeline.raw());
+        pass.draw(0..3, 0..1);
+    }
+    let img = frame.finish_and_capture().unwrap();
+
+    if let Some(err) = beul::execute(ctx.device().pop_error_scope()) {
+        panic!("{err}");
+    }
+    assert_eq!(img.get_pixel(8, 8).0, [255, 0, 0, 255]);
+}
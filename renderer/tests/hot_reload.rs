@@ -0,0 +1,60 @@
+mod common;
+
+use renderer::wgpu::{
+    Preprocessor, ReloadablePipeline, RenderPipeline, Shader, ShaderError, WgpuError,
+};
+use std::path::PathBuf;
+
+const FULLSCREEN: &str = r"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+";
+
+#[test]
+fn broken_shader_keeps_the_last_good_pipeline() {
+    let Some(ctx) = common::context() else { return };
+    let root = common::temp_dir("hot-reload");
+    let path = root.join("fullscreen.wgsl");
+    std::fs::write(&path, FULLSCREEN).unwrap();
+
+    let mut pipeline = ReloadablePipeline::new(
+        &ctx,
+        Preprocessor::new(&root),
+        "fullscreen.wgsl",
+        RenderPipeline::builder().label("fullscreen"),
+    )
+    .unwrap();
+    // Every build compiles a new shader module
+    let first: *const Shader = pipeline.pipeline().shader();
+
+    let unrelated = root.join("other.wgsl");
+    std::fs::write(&unrelated, FULLSCREEN).unwrap();
+    assert!(!pipeline.reload_if_changed(&ctx, &[unrelated]).unwrap());
+
+    let changed: Vec<PathBuf> = vec![path.clone()];
+    std::fs::write(
+        &path,
+        FULLSCREEN.replace("vec4<f32>(1.0", "vec4<f32>(1.0,,"),
+    )
+    .unwrap();
+    match pipeline.reload_if_changed(&ctx, &changed) {
+        Err(WgpuError::Shader(ShaderError::Parse(diagnostic))) => {
+            assert_eq!(diagnostic.path.as_deref(), Some(path.as_path()));
+        }
+        Err(e) => panic!("expected a parse error, got {e}"),
+        Ok(reloaded) => panic!("expected a parse error, reloaded: {reloaded}"),
+    }
+    assert!(std::ptr::eq(pipeline.pipeline().shader(), first));
+
+    std::fs::write(&path, FULLSCREEN).unwrap();
+    assert!(pipeline.reload_if_changed(&ctx, &changed).unwrap());
+    assert!(!std::ptr::eq(pipeline.pipeline().shader(), first));
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use bytemuck::{Pod, Zeroable};
+use renderer::wgpu::{
+    BufferError, MeshAllocation, MeshArena, RenderPipeline, ShaderSource, Vertex, WgpuContext,
+    WgpuError,
+};
+
+const COLORED: &str = r"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    return VertexOutput(vec4<f32>(position, 0.0, 1.0), color);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+";
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+struct ColorVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+/// A quad spanning the full height between `left` and `right` in clip space
+fn quad(left: f32, right: f32, color: [f32; 4]) -> [ColorVertex; 4] {
+    [[left, -1.0], [right, -1.0], [right, 1.0], [left, 1.0]]
+        .map(|position| ColorVertex { position, color })
+}
+
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+/// Draws a single mesh of `arena` over black
+fn draw(
+    ctx: &WgpuContext,
+    pipeline: &RenderPipeline,
+    arena: &MeshArena<ColorVertex>,
+    mesh: MeshAllocation,
+) -> image::RgbaImage {
+    ctx.device().push_error_scope(wgpu::ErrorFilter::Validation);
+    let mut frame = ctx.begin_frame().unwrap().unwrap();
+    {
+        let mut pass = frame.begin_render_pass_with(None, Some(wgpu::Color::BLACK), None);
+        pass.set_pipeline(pipeline.raw());
+        arena.bind(&mut pass, 0);
+        mesh.draw(&mut pass, 0..1);
+    }
+    let img = frame.finish_and_capture().unwrap();
+
+    if let Some(err) = beul::execute(ctx.device().pop_error_scope()) {
+        panic!("{err}");
+    }
+    img
+}
+
+#[test]
+fn defragment_moves_meshes_with_their_data() {
+    let Some(ctx) = common::context() else { return };
+    // 6 byte vertices take steps of 2 to stay on whole words
+    let mut arena = MeshArena::<[u16; 3]>::new(ctx.device(), 32, 32, 1, Some("arena")).unwrap();
+
+    // Mesh `i` has `i + 1` vertices and `i + 2` indices, all tagged with `i`
+    let vertices = |i: u16| -> Vec<[u16; 3]> { (0..=i).map(|j| [i, j, 7]).collect() };
+    let indices = |i: u16| -> Vec<u32> {
+        (0..u32::from(i) + 2)
+            .map(|j| 100 * u32::from(i) + j)
+            .collect()
+    };
+    let handles: Vec<_> = (0..5)
+        .map(|i| {
+            arena
+                .insert(ctx.queue(), &vertices(i), &indices(i))
+                .unwrap()
+        })
+        .collect();
+    let offsets = |arena: &MeshArena<_>, i: usize| {
+        let mesh = arena.get(handles[i]).unwrap();
+        (mesh.vertex_offset, mesh.index_offset)
+    };
+    assert_eq!(offsets(&arena, 4), (12, 14));
+
+    assert!(arena.remove(handles[1]));
+    assert!(arena.remove(handles[3]));
+    // Both holes and the unused end
+    assert_eq!(arena.stats().vertices.free_ranges, 3);
+
+    arena.defragment(&ctx).unwrap();
+
+    assert_eq!(arena.get(handles[1]), None);
+    assert_eq!(arena.get(handles[3]), None);
+    // Packed in their previous order, vertex counts rounded up to even
+    assert_eq!(offsets(&arena, 0), (0, 0));
+    assert_eq!(offsets(&arena, 2), (2, 2));
+    assert_eq!(offsets(&arena, 4), (6, 6));
+    let stats = arena.stats();
+    assert_eq!(
+        (stats.meshes, stats.vertices.used, stats.indices.used),
+        (3, 12, 12)
+    );
+    assert_eq!(
+        (stats.vertices.free_ranges, stats.indices.free_ranges),
+        (1, 1)
+    );
+
+    let vertex_data: Vec<[u16; 3]> = arena.vertex_buffer().read(&ctx).unwrap();
+    let index_data: Vec<u32> = arena.index_buffer().read(&ctx).unwrap();
+    for i in [0, 2, 4] {
+        let mesh = arena.get(handles[usize::from(i)]).unwrap();
+        let range = |range: std::ops::Range<u32>| range.start as usize..range.end as usize;
+        assert_eq!(vertex_data[range(mesh.vertices())], vertices(i), "mesh {i}");
+        assert_eq!(index_data[range(mesh.indices())], indices(i), "mesh {i}");
+    }
+
+    // New meshes go after the compacted ones
+    let handle = arena
+        .insert(ctx.queue(), &vertices(0), &indices(0))
+        .unwrap();
+    let mesh = arena.get(handle).unwrap();
+    assert_eq!((mesh.vertex_offset, mesh.index_offset), (12, 12));
+}
+
+#[test]
+fn allocations_start_at_the_alignment() {
+    let Some(ctx) = common::context() else { return };
+    // 256 bytes are 64 vertices of 12 bytes and 64 indices
+    let mut arena = MeshArena::<[f32; 3]>::new(ctx.device(), 256, 256, 256, None).unwrap();
+
+    let mut vertex_offsets = Vec::new();
+    for _ in 0..4 {
+        let handle = arena
+            .insert(ctx.queue(), &[[1.0; 3]; 3], &[0, 1, 2])
+            .unwrap();
+        let mesh = arena.get(handle).unwrap();
+        assert_eq!(mesh.index_offset, mesh.vertex_offset);
+        vertex_offsets.push(mesh.vertex_offset);
+    }
+    assert_eq!(vertex_offsets, [0, 64, 128, 192]);
+    assert_eq!(arena.stats().vertices.used, 256);
+
+    let result = arena.insert(ctx.queue(), &[[1.0; 3]], &[]);
+    assert!(
+        matches!(
+            result,
+            Err(WgpuError::Buffer(BufferError::OutOfSpace {
+                requested: 1,
+                largest_free: 0,
+                ..
+            }))
+        ),
+        "expected the arena to be full, got {result:?}"
+    );
+}
+
+#[test]
+fn meshes_are_drawn_with_their_base_vertex() {
+    let Some(ctx) = common::context() else { return };
+    let pipeline = RenderPipeline::builder()
+        .label("mesh arena")
+        .vertex_buffers(&[ColorVertex::desc()])
+        .cull_mode(None)
+        .depth_stencil(None)
+        .build(&ctx, ShaderSource::SourceCode(COLORED))
+        .unwrap();
+    let mut arena = MeshArena::new(ctx.device(), 16, 16, 4, Some("quads")).unwrap();
+
+    let red = [1.0, 0.0, 0.0, 1.0];
+    let green = [0.0, 1.0, 0.0, 1.0];
+    let left = arena
+        .insert(ctx.queue(), &quad(-1.0, 0.0, red), &QUAD_INDICES)
+        .unwrap();
+    let right = arena
+        .insert(ctx.queue(), &quad(0.0, 1.0, green), &QUAD_INDICES)
+        .unwrap();
+
+    // Without the base vertex the indices would pick the left quad
+    let mesh = arena.get(right).unwrap();
+    assert_eq!(mesh.base_vertex(), 4);
+    let img = draw(&ctx, &pipeline, &arena, mesh);
+    assert_eq!(img.get_pixel(4, 8).0, [0, 0, 0, 255]);
+    assert_eq!(img.get_pixel(12, 8).0, [0, 255, 0, 255]);
+
+    arena.remove(left);
+    arena.defragment(&ctx).unwrap();
+    let mesh = arena.get(right).unwrap();
+    assert_eq!(mesh.base_vertex(), 0);
+    let img = draw(&ctx, &pipeline, &arena, mesh);
+    assert_eq!(img.get_pixel(4, 8).0, [0, 0, 0, 255]);
+    assert_eq!(img.get_pixel(12, 8).0, [0, 255, 0, 255]);
+}
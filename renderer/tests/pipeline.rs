@@ -0,0 +1,219 @@
+mod common;
+
+use renderer::wgpu::{
+    PipelineError, RenderPipeline, ShaderError, ShaderSource, ShaderStage, VertexInputType,
+    WgpuError,
+};
+
+const COLORED: &str = r"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    return VertexOutput(vec4<f32>(position, 1.0), color);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+";
+
+/// Has no `fs_main`, so the fragment entry point always has to be picked
+const FULLSCREEN: &str = r"
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(position, 1.0);
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_red() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_blue() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 1.0, 1.0);
+}
+";
+
+const VEC4_F32: VertexInputType = VertexInputType {
+    kind: naga::ScalarKind::Float,
+    width: 4,
+    components: 4,
+};
+
+fn build_colored(
+    ctx: &common::TestContext,
+    attributes: &[wgpu::VertexAttribute],
+) -> Result<RenderPipeline, WgpuError> {
+    RenderPipeline::builder()
+        .label("colored")
+        .vertex_buffers(&[wgpu::VertexBufferLayout {
+            array_stride: 32,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        }])
+        .build(ctx, ShaderSource::SourceCode(COLORED))
+}
+
+#[test]
+fn matching_vertex_inputs_build() {
+    let Some(ctx) = common::context() else { return };
+
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3, 1 => Unorm8x4];
+    build_colored(&ctx, &attributes).unwrap();
+}
+
+#[test]
+fn vertex_input_of_wrong_format_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3, 1 => Uint32x4];
+    match build_colored(&ctx, &attributes) {
+        Err(WgpuError::Pipeline(PipelineError::VertexInputMismatch {
+            label,
+            location,
+            expected,
+            provided,
+        })) => {
+            assert_eq!(label.as_deref(), Some("colored"));
+            assert_eq!(location, 1);
+            assert_eq!(expected, VEC4_F32);
+            assert_eq!(provided, wgpu::VertexFormat::Uint32x4);
+        }
+        Err(e) => panic!("expected a vertex input mismatch, got {e}"),
+        Ok(_) => panic!("expected a vertex input mismatch, the pipeline was built"),
+    }
+}
+
+#[test]
+fn missing_vertex_input_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3];
+    match build_colored(&ctx, &attributes) {
+        Err(WgpuError::Pipeline(PipelineError::MissingVertexInput {
+            label,
+            location,
+            expected,
+        })) => {
+            assert_eq!(label.as_deref(), Some("colored"));
+            assert_eq!(location, 1);
+            assert_eq!(expected, VEC4_F32);
+        }
+        Err(e) => panic!("expected a missing vertex input, got {e}"),
+        Ok(_) => panic!("expected a missing vertex input, the pipeline was built"),
+    }
+}
+
+#[test]
+fn missing_entry_point_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+
+    let result = RenderPipeline::builder()
+        .label("colored")
+        .vertex_entry("vs_missing")
+        .build(&ctx, ShaderSource::SourceCode(COLORED));
+    match result {
+        Err(WgpuError::Shader(ShaderError::MissingEntryPoint { label, name })) => {
+            assert_eq!(label.as_deref(), Some("shader for pipeline colored"));
+            assert_eq!(name, "vs_missing");
+        }
+        Err(e) => panic!("expected a missing entry point, got {e}"),
+        Ok(_) => panic!("expected a missing entry point, the pipeline was built"),
+    }
+}
+
+#[test]
+fn entry_point_of_wrong_stage_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+    let result = RenderPipeline::builder()
+        .vertex_buffers(&[wgpu::VertexBufferLayout {
+            array_stride: 32,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &attributes,
+        }])
+        .fragment_entry("vs_main")
+        .build(&ctx, ShaderSource::SourceCode(COLORED));
+    match result {
+        Err(WgpuError::Shader(ShaderError::WrongStage {
+            name,
+            expected,
+            found,
+            ..
+        })) => {
+            assert_eq!(name, "vs_main");
+            assert_eq!(expected, ShaderStage::Fragment);
+            assert_eq!(found, ShaderStage::Vertex);
+        }
+        Err(e) => panic!("expected a wrong stage, got {e}"),
+        Ok(_) => panic!("expected a wrong stage, the pipeline was built"),
+    }
+}
+
+#[test]
+fn non_default_entry_points_are_used() {
+    let Some(ctx) = common::context() else { return };
+
+    // The default `vs_main` needs a vertex buffer and there is no `fs_main`
+    let result = RenderPipeline::builder()
+        .vertex_entry("vs_fullscreen")
+        .build(&ctx, ShaderSource::SourceCode(FULLSCREEN));
+    match result {
+        Err(WgpuError::Shader(ShaderError::MissingEntryPoint { name, .. })) => {
+            assert_eq!(name, "fs_main");
+        }
+        Err(e) => panic!("expected a missing entry point, got {e}"),
+        Ok(_) => panic!("expected a missing entry point, the pipeline was built"),
+    }
+    let result = RenderPipeline::builder()
+        .fragment_entry("fs_blue")
+        .build(&ctx, ShaderSource::SourceCode(FULLSCREEN));
+    assert!(
+        matches!(
+            result,
+            Err(WgpuError::Pipeline(PipelineError::MissingVertexInput {
+                location: 0,
+                ..
+            }))
+        ),
+        "expected the default vertex entry point to need a vertex buffer"
+    );
+
+    RenderPipeline::builder()
+        .vertex_entry("vs_fullscreen")
+        .fragment_entry("fs_blue")
+        .build(&ctx, ShaderSource::SourceCode(FULLSCREEN))
+        .unwrap();
+}
+
+#[test]
+fn vertex_only_pipelines_build() {
+    let Some(ctx) = common::context() else { return };
+
+    RenderPipeline::builder()
+        .label("depth prepass")
+        .vertex_entry("vs_fullscreen")
+        .no_fragment()
+        .depth_stencil(Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }))
+        .build(&ctx, ShaderSource::SourceCode(FULLSCREEN))
+        .unwrap();
+}
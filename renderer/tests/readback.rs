@@ -0,0 +1,73 @@
+mod common;
+
+use renderer::wgpu::{Texture, WgpuContext};
+
+/// Creates a 1x1 texture of `format` holding `texel` and reads it back
+fn read_texel(ctx: &WgpuContext, format: wgpu::TextureFormat, texel: [u8; 4]) -> [u8; 4] {
+    let size = wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+    let texture = ctx.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some("readback test texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    ctx.queue().write_texture(
+        texture.as_image_copy(),
+        &texel,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4),
+            rows_per_image: None,
+        },
+        size,
+    );
+
+    let img = Texture::read_texture_to_image(ctx.device(), ctx.queue(), &texture).unwrap();
+    img.get_pixel(0, 0).0
+}
+
+#[test]
+fn linear_8bit_formats_get_srgb_encoded() {
+    let Some(ctx) = common::context() else { return };
+
+    // Linear 0.5 and 0.25 are sRGB 188 and 137, alpha stays linear
+    let expected = [188, 137, 255, 128];
+    assert_eq!(
+        read_texel(&ctx, wgpu::TextureFormat::Rgba8Unorm, [128, 64, 255, 128]),
+        expected
+    );
+    assert_eq!(
+        read_texel(&ctx, wgpu::TextureFormat::Bgra8Unorm, [255, 64, 128, 128]),
+        expected
+    );
+}
+
+#[test]
+fn srgb_8bit_formats_are_copied() {
+    let Some(ctx) = common::context() else { return };
+
+    assert_eq!(
+        read_texel(
+            &ctx,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            [128, 64, 255, 128]
+        ),
+        [128, 64, 255, 128]
+    );
+    assert_eq!(
+        read_texel(
+            &ctx,
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            [255, 64, 128, 128]
+        ),
+        [128, 64, 255, 128]
+    );
+}
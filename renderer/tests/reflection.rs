@@ -0,0 +1,119 @@
+mod common;
+
+use renderer::wgpu::{ComputePipeline, ComputePipelineBuilder, ShaderSource, TypedBuffer};
+
+const SAMPLE: &str = r"
+@group(0) @binding(0) var depth: texture_2d<f32>;
+@group(0) @binding(1) var depth_sampler: sampler;
+@group(0) @binding(2) var<storage, read_write> out: array<f32>;
+
+@compute @workgroup_size(1)
+fn cs_main() {
+    out[0] = textureSampleLevel(depth, depth_sampler, vec2<f32>(0.5), 0.0).r;
+}
+";
+
+/// Samples a 1x1 `r32float` texture holding 0.75 with a pipeline from
+/// `builder`, `None` if creating the bind group fails validation
+fn sample_r32float(ctx: &common::TestContext, builder: ComputePipelineBuilder) -> Option<f32> {
+    let device = ctx.device();
+    let pipeline: ComputePipeline = builder
+        .build(ctx, ShaderSource::SourceCode(SAMPLE))
+        .unwrap();
+
+    let size = wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("r32float texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    ctx.queue().write_texture(
+        texture.as_image_copy(),
+        &0.75f32.to_ne_bytes(),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4),
+            rows_per_image: None,
+        },
+        size,
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // Nearest filtering, so the sampler is non-filtering
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+    let out = TypedBuffer::<f32>::new(
+        device,
+        1,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        Some("sampled value"),
+    )
+    .unwrap();
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("r32float bind group"),
+        layout: pipeline.bind_group_layout(0).unwrap(),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+            out.buffer().bind_group_entry(2),
+        ],
+    });
+    if beul::execute(device.pop_error_scope()).is_some() {
+        return None;
+    }
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        pass.set_bind_group(0, &bind_group, &[]);
+        pipeline.dispatch(&mut pass, 1);
+    }
+    ctx.queue().submit([encoder.finish()]);
+
+    Some(out.read(ctx).unwrap()[0])
+}
+
+#[test]
+fn reflected_layouts_assume_filterable_textures() {
+    let Some(ctx) = common::context() else { return };
+
+    assert_eq!(sample_r32float(&ctx, ComputePipeline::builder()), None);
+}
+
+#[test]
+fn binding_type_overrides_allow_unfilterable_textures() {
+    let Some(ctx) = common::context() else { return };
+
+    let builder = ComputePipeline::builder()
+        .binding_type(
+            0,
+            0,
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+        )
+        .binding_type(
+            0,
+            1,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+        );
+
+    assert_eq!(sample_r32float(&ctx, builder), Some(0.75));
+}
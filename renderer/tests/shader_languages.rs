@@ -0,0 +1,168 @@
+mod common;
+
+use renderer::wgpu::{
+    Buffer, ComputePipeline, RenderPipeline, Shader, ShaderError, ShaderSource, ShaderStage,
+    WgpuContext, WgpuError,
+};
+use std::path::{Path, PathBuf};
+
+/// Hand-assembled `@compute @workgroup_size(2, 1, 1) fn main() {}`
+#[rustfmt::skip]
+const EMPTY_COMPUTE_SPIRV: [u32; 35] = [
+    // Magic, version 1.0, generator, id bound, schema
+    0x0723_0203, 0x0001_0000, 0, 5, 0,
+    // OpCapability Shader
+    0x0002_0011, 1,
+    // OpMemoryModel Logical GLSL450
+    0x0003_000e, 0, 1,
+    // OpEntryPoint GLCompute %1 "main"
+    0x0005_000f, 5, 1, u32::from_le_bytes(*b"main"), 0,
+    // OpExecutionMode %1 LocalSize 2 1 1
+    0x0006_0010, 1, 17, 2, 1, 1,
+    // %2 = OpTypeVoid, %3 = OpTypeFunction %2
+    0x0002_0013, 2,
+    0x0003_0021, 3, 2,
+    // %1 = OpFunction %2 None %3, %4 = OpLabel, OpReturn, OpFunctionEnd
+    0x0005_0036, 2, 1, 0, 3,
+    0x0002_00f8, 4,
+    0x0001_00fd,
+    0x0001_0038,
+];
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/shaders")
+        .join(name)
+}
+
+fn spirv_bytes() -> Vec<u8> {
+    EMPTY_COMPUTE_SPIRV
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
+fn entry_stage(shader: &Shader, name: &str) -> ShaderStage {
+    shader
+        .reflection()
+        .unwrap()
+        .entry_point(name)
+        .unwrap()
+        .stage
+}
+
+/// Runs `fill.comp` over `len` elements and reads them back
+fn fill(ctx: &WgpuContext, shader: Shader, len: u32) -> Vec<u32> {
+    let pipeline = ComputePipeline::new(ctx, ShaderSource::Struct(shader), None).unwrap();
+    let out = Buffer::new(
+        ctx.device(),
+        u64::from(len) * 4,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        false,
+        Some("glsl test output"),
+    )
+    .unwrap();
+    let bind_group = ctx.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: pipeline.bind_group_layout(0).unwrap(),
+        entries: &[out.bind_group_entry(0)],
+    });
+
+    let mut encoder = ctx
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        pass.set_bind_group(0, &bind_group, &[]);
+        pipeline.dispatch(&mut pass, len);
+    }
+    ctx.queue().submit([encoder.finish()]);
+
+    out.read::<u32>(ctx).unwrap()
+}
+
+#[test]
+fn glsl_stage_is_picked_by_extension() {
+    let Some(ctx) = common::context() else { return };
+
+    let compute = Shader::from_path(ctx.device(), fixture("fill.comp")).unwrap();
+    assert_eq!(entry_stage(&compute, "main"), ShaderStage::Compute);
+    assert_eq!(compute.compute_entry(), "main");
+    assert_eq!(fill(&ctx, compute, 4), [0, 3, 6, 9]);
+
+    let vertex = Shader::from_path(ctx.device(), fixture("color.vert")).unwrap();
+    let fragment = Shader::from_path(ctx.device(), fixture("color.frag")).unwrap();
+    assert_eq!(entry_stage(&vertex, "main"), ShaderStage::Vertex);
+    assert_eq!(entry_stage(&fragment, "main"), ShaderStage::Fragment);
+
+    RenderPipeline::builder()
+        .vertex_buffers(&[wgpu::VertexBufferLayout {
+            array_stride: 8,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+        }])
+        .build_separate(
+            &ctx,
+            ShaderSource::Struct(vertex),
+            ShaderSource::Struct(fragment),
+        )
+        .unwrap();
+}
+
+#[test]
+fn glsl_source_is_parsed_for_the_given_stage() {
+    let Some(ctx) = common::context() else { return };
+
+    let source = std::fs::read_to_string(fixture("fill.comp")).unwrap();
+    let shader =
+        Shader::from_glsl(ctx.device(), &source, ShaderStage::Compute, Some("fill")).unwrap();
+    assert_eq!(fill(&ctx, shader, 3), [0, 3, 6]);
+
+    // `local_size_x` and `gl_GlobalInvocationID` only exist in compute shaders
+    let result = Shader::from_glsl(ctx.device(), &source, ShaderStage::Vertex, Some("fill"));
+    assert!(
+        matches!(result, Err(WgpuError::Shader(_))),
+        "a compute shader should not pass as a vertex shader"
+    );
+}
+
+#[test]
+fn spirv_is_loaded_from_bytes_and_files() {
+    let Some(ctx) = common::context() else { return };
+    let bytes = spirv_bytes();
+
+    let pipeline = ComputePipeline::new(&ctx, ShaderSource::SpirV(&bytes), None).unwrap();
+    assert_eq!(pipeline.entry_point(), "main");
+    assert_eq!(pipeline.workgroup_size(), [2, 1, 1]);
+
+    let dir = common::temp_dir("shader-languages");
+    let path = dir.join("empty.spv");
+    std::fs::write(&path, &bytes).unwrap();
+    let shader = Shader::from_path(ctx.device(), &path).unwrap();
+    assert_eq!(shader.label(), Some(path.display().to_string().as_str()));
+    assert_eq!(entry_stage(&shader, "main"), ShaderStage::Compute);
+    assert_eq!(
+        shader
+            .reflection()
+            .unwrap()
+            .entry_point("main")
+            .unwrap()
+            .workgroup_size,
+        [2, 1, 1]
+    );
+}
+
+#[test]
+fn unknown_extension_is_an_error() {
+    let Some(ctx) = common::context() else { return };
+
+    for name in ["lit.hlsl", "lit"] {
+        match Shader::from_path(ctx.device(), name) {
+            Err(WgpuError::Shader(ShaderError::UnknownLanguage { path })) => {
+                assert_eq!(path, Path::new(name))
+            }
+            Err(e) => panic!("expected an unknown language, got {e}"),
+            Ok(_) => panic!("expected an unknown language, {name} was loaded"),
+        }
+    }
+}
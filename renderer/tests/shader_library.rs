@@ -0,0 +1,95 @@
+mod common;
+
+use renderer::wgpu::{Buffer, ComputePipeline, ShaderLibrary, ShaderSource, WgpuContext};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const SCALE: &str = r"
+@group(0) @binding(0)
+var<storage, read_write> out: array<u32>;
+
+@compute @workgroup_size(1)
+fn cs_main() {
+    out[0] = LIGHT_COUNT * 2u;
+}
+";
+
+/// Writes the test shader into a fresh directory and returns it
+fn shader_root(name: &str) -> PathBuf {
+    let root = common::temp_dir(name);
+    std::fs::write(root.join("scale.wgsl"), SCALE).unwrap();
+    root
+}
+
+/// Runs the variant's `cs_main` once and returns what it wrote
+fn run(ctx: &WgpuContext, library: &mut ShaderLibrary, defines: &[&str]) -> u32 {
+    let shader = library.get(ctx.device(), "scale.wgsl", defines).unwrap();
+    let pipeline =
+        ComputePipeline::new(ctx, ShaderSource::Shared(Arc::clone(&shader)), None).unwrap();
+
+    let out = Buffer::new(
+        ctx.device(),
+        4,
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        false,
+        Some("shader library test output"),
+    )
+    .unwrap();
+    let bind_group = ctx.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: pipeline.bind_group_layout(0).unwrap(),
+        entries: &[out.bind_group_entry(0)],
+    });
+
+    let mut encoder = ctx
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        pass.set_bind_group(0, &bind_group, &[]);
+        pipeline.dispatch(&mut pass, 1);
+    }
+    ctx.queue().submit([encoder.finish()]);
+
+    out.read::<u32>(ctx).unwrap()[0]
+}
+
+#[test]
+fn define_values_reach_the_shader_as_constants() {
+    let Some(ctx) = common::context() else { return };
+    let root = shader_root("shader-library");
+    let mut library = ShaderLibrary::new(&root);
+
+    assert_eq!(run(&ctx, &mut library, &["LIGHT_COUNT=3u"]), 6);
+    assert_eq!(run(&ctx, &mut library, &["LIGHT_COUNT=5u"]), 10);
+    assert_eq!(library.variant_count(), 2);
+}
+
+#[test]
+fn spellings_of_a_path_share_a_variant() {
+    let Some(ctx) = common::context() else { return };
+    let root = shader_root("shader-library-paths");
+    std::fs::create_dir(root.join("sub")).unwrap();
+    let mut library = ShaderLibrary::new(&root);
+
+    let shader = library
+        .get(ctx.device(), "scale.wgsl", &["LIGHT_COUNT=1u"])
+        .unwrap();
+    for path in [
+        PathBuf::from("./scale.wgsl"),
+        PathBuf::from("sub/../scale.wgsl"),
+        root.join("scale.wgsl"),
+    ] {
+        assert!(library.contains(&path, &["LIGHT_COUNT=1u"]));
+        let same = library
+            .get(ctx.device(), &path, &["LIGHT_COUNT=1u"])
+            .unwrap();
+        assert!(
+            Arc::ptr_eq(&shader, &same),
+            "{} got its own variant",
+            path.display()
+        );
+    }
+    assert_eq!(library.variant_count(), 1);
+    assert_eq!(library.variant_count_of("./scale.wgsl"), 1);
+}
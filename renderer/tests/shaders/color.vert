@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 0) out vec4 color;
+
+void main() {
+    color = vec4(position * 0.5 + 0.5, 0.0, 1.0);
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,342 @@
+mod common;
+
+use renderer::wgpu::{
+    Buffer, BufferError, StagingBelt, Texture, TextureError, UploadStats, WgpuContext, WgpuError,
+};
+
+const SIZE_16: wgpu::BufferSize = match wgpu::BufferSize::new(16) {
+    Some(size) => size,
+    None => unreachable!(),
+};
+
+fn buffer(ctx: &WgpuContext, size: u64, usages: wgpu::BufferUsages) -> Buffer {
+    Buffer::new(ctx.device(), size, usages, false, Some("belt target")).unwrap()
+}
+
+/// A zeroed `Rgba8UnormSrgb` texture, read back as stored
+fn texture(ctx: &WgpuContext, width: u32, height: u32) -> Texture {
+    Texture::new(
+        ctx.device(),
+        &wgpu::TextureDescriptor {
+            label: Some("belt texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        },
+    )
+    .unwrap()
+}
+
+fn encoder(ctx: &WgpuContext) -> wgpu::CommandEncoder {
+    ctx.device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor::default())
+}
+
+/// Submits the frame's copies and waits until its chunks are mapped again
+fn end_frame(ctx: &WgpuContext, belt: &mut StagingBelt, encoder: wgpu::CommandEncoder) {
+    belt.finish();
+    ctx.queue().submit([encoder.finish()]);
+    belt.recall();
+    ctx.device().poll(wgpu::Maintain::Wait);
+}
+
+#[test]
+fn buffer_and_texture_writes_arrive() {
+    let Some(ctx) = common::context() else { return };
+    let mut belt = StagingBelt::new(1024);
+
+    let target = buffer(
+        &ctx,
+        32,
+        wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+    );
+    let texture = texture(&ctx, 5, 3);
+
+    let mut encoder = encoder(&ctx);
+    belt.write_buffer(ctx.device(), &mut encoder, &target, 16, SIZE_16)
+        .unwrap()
+        .copy_from_slice(&[7; 16]);
+    let region = wgpu::Extent3d {
+        width: 3,
+        height: 2,
+        depth_or_array_layers: 1,
+    };
+    let mut write = belt
+        .write_texture(
+            ctx.device(),
+            &mut encoder,
+            &texture,
+            0,
+            wgpu::Origin3d { x: 1, y: 1, z: 0 },
+            region,
+        )
+        .unwrap();
+    assert_eq!((write.rows(), write.row_size()), (2, 12));
+    let texels: Vec<u8> = (1..=24).collect();
+    write.copy_from_slice(&texels);
+    drop(write);
+    end_frame(&ctx, &mut belt, encoder);
+
+    let mut expected = [0; 32];
+    expected[16..].fill(7);
+    assert_eq!(target.read::<u8>(&ctx).unwrap(), expected);
+
+    let image = texture.read_to_image(ctx.device(), ctx.queue()).unwrap();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let expected = if (1..4).contains(&x) && (1..3).contains(&y) {
+            let i = 4 * (3 * (y - 1) + x - 1) as u8;
+            [i + 1, i + 2, i + 3, i + 4]
+        } else {
+            [0; 4]
+        };
+        assert_eq!(pixel.0, expected, "texel {x}, {y}");
+    }
+}
+
+#[test]
+fn chunks_are_reused_in_the_next_frame() {
+    let Some(ctx) = common::context() else { return };
+    let mut belt = StagingBelt::new(1024);
+    let target = buffer(&ctx, 64, wgpu::BufferUsages::COPY_DST);
+    let texture = texture(&ctx, 3, 2);
+    let region = wgpu::Extent3d {
+        width: 3,
+        height: 2,
+        depth_or_array_layers: 1,
+    };
+
+    for frame in 0..3 {
+        let mut encoder = encoder(&ctx);
+        belt.write_buffer(ctx.device(), &mut encoder, &target, 0, SIZE_16)
+            .unwrap()
+            .copy_from_slice(&[1; 16]);
+        belt.write_texture(
+            ctx.device(),
+            &mut encoder,
+            &texture,
+            0,
+            wgpu::Origin3d::ZERO,
+            region,
+        )
+        .unwrap()
+        .copy_from_slice(&[2; 24]);
+        assert_eq!(belt.stats().writes, 2);
+        end_frame(&ctx, &mut belt, encoder);
+
+        // Both writes share one chunk, the texture rows are padded to 256 bytes in it
+        let created = u32::from(frame == 0);
+        assert_eq!(
+            belt.last_frame_stats(),
+            UploadStats {
+                bytes: 16 + 24,
+                writes: 2,
+                chunks_created: created,
+                bytes_allocated: 1024 * u64::from(created),
+            },
+            "frame {frame}"
+        );
+        assert_eq!(belt.stats(), UploadStats::default());
+    }
+}
+
+#[test]
+fn oversized_chunks_are_not_kept() {
+    let Some(ctx) = common::context() else { return };
+    let mut belt = StagingBelt::new(256);
+    let target = buffer(&ctx, 4096, wgpu::BufferUsages::COPY_DST);
+    let large = wgpu::BufferSize::new(4096).unwrap();
+
+    for frame in 0..2 {
+        let mut encoder = encoder(&ctx);
+        belt.write_buffer(ctx.device(), &mut encoder, &target, 0, large)
+            .unwrap()
+            .fill(1);
+        end_frame(&ctx, &mut belt, encoder);
+
+        let stats = belt.last_frame_stats();
+        assert_eq!(
+            (stats.chunks_created, stats.bytes_allocated),
+            (1, 4096),
+            "frame {frame}"
+        );
+    }
+
+    // Writes that fit the chunk size still get their chunk back
+    for frame in 0..2 {
+        let mut encoder = encoder(&ctx);
+        belt.write_buffer(ctx.device(), &mut encoder, &target, 0, SIZE_16)
+            .unwrap()
+            .fill(1);
+        end_frame(&ctx, &mut belt, encoder);
+        assert_eq!(
+            belt.last_frame_stats().chunks_created,
+            u32::from(frame == 0)
+        );
+    }
+}
+
+#[test]
+fn invalid_buffer_writes_are_errors() {
+    let Some(ctx) = common::context() else { return };
+    let mut belt = StagingBelt::new(1024);
+    let mut encoder = encoder(&ctx);
+    let target = buffer(&ctx, 16, wgpu::BufferUsages::COPY_DST);
+
+    let result = belt
+        .write_buffer(ctx.device(), &mut encoder, &target, 8, SIZE_16)
+        .map(drop);
+    assert!(
+        matches!(
+            result,
+            Err(WgpuError::Buffer(BufferError::OutOfBounds {
+                offset: 8,
+                size: 16,
+                buffer_size: 16,
+                ..
+            }))
+        ),
+        "expected an out of bounds write, got {result:?}"
+    );
+
+    let size = wgpu::BufferSize::new(4).unwrap();
+    let result = belt
+        .write_buffer(ctx.device(), &mut encoder, &target, 2, size)
+        .map(drop);
+    assert!(
+        matches!(
+            result,
+            Err(WgpuError::Buffer(BufferError::Unaligned {
+                offset: 2,
+                size: 4,
+                ..
+            }))
+        ),
+        "expected an unaligned write, got {result:?}"
+    );
+
+    let target = buffer(&ctx, 16, wgpu::BufferUsages::STORAGE);
+    let result = belt
+        .write_buffer(ctx.device(), &mut encoder, &target, 0, SIZE_16)
+        .map(drop);
+    assert!(
+        matches!(
+            result,
+            Err(WgpuError::Buffer(BufferError::MissingUsage {
+                usage: wgpu::BufferUsages::COPY_DST,
+                ..
+            }))
+        ),
+        "expected a missing usage, got {result:?}"
+    );
+
+    // Nothing was staged
+    assert_eq!(belt.stats(), UploadStats::default());
+}
+
+#[test]
+fn invalid_texture_writes_are_errors() {
+    let Some(ctx) = common::context() else { return };
+    let mut belt = StagingBelt::new(1024);
+    let mut encoder = encoder(&ctx);
+    let region = |width, height| wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    let texture = texture(&ctx, 4, 4);
+    for (mip_level, origin, extent) in [
+        (0, wgpu::Origin3d { x: 2, y: 0, z: 0 }, region(3, 1)),
+        (0, wgpu::Origin3d::ZERO, region(0, 1)),
+        (1, wgpu::Origin3d::ZERO, region(1, 1)),
+    ] {
+        let result = belt
+            .write_texture(
+                ctx.device(),
+                &mut encoder,
+                &texture,
+                mip_level,
+                origin,
+                extent,
+            )
+            .map(drop);
+        assert!(
+            matches!(
+                result,
+                Err(WgpuError::Texture(TextureError::InvalidRegion { .. }))
+            ),
+            "expected an invalid region for {extent:?} at {origin:?}, got {result:?}"
+        );
+    }
+
+    let target = Texture::create_render_target(
+        ctx.device(),
+        4,
+        4,
+        wgpu::TextureFormat::Rgba8Unorm,
+        Some("no copy"),
+    )
+    .unwrap();
+    let result = belt
+        .write_texture(
+            ctx.device(),
+            &mut encoder,
+            &target,
+            0,
+            wgpu::Origin3d::ZERO,
+            region(1, 1),
+        )
+        .map(drop);
+    assert!(
+        matches!(
+            result,
+            Err(WgpuError::Texture(TextureError::MissingUsage {
+                usage: wgpu::TextureUsages::COPY_DST,
+                ..
+            }))
+        ),
+        "expected a missing usage, got {result:?}"
+    );
+
+    let depth = Texture::new(
+        ctx.device(),
+        &wgpu::TextureDescriptor {
+            label: Some("depth"),
+            size: region(4, 4),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+    )
+    .unwrap();
+    let result = belt
+        .write_texture(
+            ctx.device(),
+            &mut encoder,
+            &depth,
+            0,
+            wgpu::Origin3d::ZERO,
+            region(1, 1),
+        )
+        .map(drop);
+    assert!(
+        matches!(
+            result,
+            Err(WgpuError::Texture(TextureError::UnsupportedFormat { .. }))
+        ),
+        "expected an unsupported format, got {result:?}"
+    );
+
+    assert_eq!(belt.stats(), UploadStats::default());
+}
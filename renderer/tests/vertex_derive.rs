@@ -0,0 +1,68 @@
+use bytemuck::{Pod, Zeroable};
+use renderer::wgpu::Vertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+struct MeshVertex {
+    position: [f32; 3],
+    #[vertex(normalized)]
+    color: [u8; 4],
+    #[vertex(skip)]
+    _padding: u32,
+    #[vertex(format = "Float16x2")]
+    uv: [u16; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+#[vertex(instance)]
+struct Instance {
+    #[vertex(location = 5)]
+    model: [[f32; 4]; 4],
+    id: u32,
+}
+
+#[test]
+fn derived_layout_matches_hand_written_one() {
+    let expected = wgpu::VertexBufferLayout {
+        array_stride: 24,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Unorm8x4,
+                offset: 12,
+                shader_location: 1,
+            },
+            // After the skipped padding
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float16x2,
+                offset: 20,
+                shader_location: 2,
+            },
+        ],
+    };
+
+    assert_eq!(MeshVertex::desc(), expected);
+}
+
+#[test]
+fn matrices_take_one_location_per_row() {
+    let expected = wgpu::VertexBufferLayout {
+        array_stride: 68,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x4,
+            9 => Uint32,
+        ],
+    };
+
+    assert_eq!(Instance::desc(), expected);
+}
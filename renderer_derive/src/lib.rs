@@ -0,0 +1,403 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{quote, quote_spanned};
+use std::collections::BTreeMap;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Type};
+
+/// Derives `renderer::wgpu::Vertex` for a `#[repr(C)]` struct that implements `bytemuck::Pod`
+///
+/// Each field becomes one attribute with its format inferred from the field
+/// type: `f32`, `u32`, `i32` and `f64` scalars and arrays of them up to four
+/// elements, as well as two and four element arrays of `u8`, `i8`, `u16` and
+/// `i16`. Arrays of arrays, e.g. `[[f32; 4]; 4]`, take one location per row.
+/// Locations are assigned in field order starting at 0, each can only be
+/// taken by one field.
+///
+/// Field attributes:
+/// - `#[vertex(location = 3)]` sets the location, the following fields continue from there
+/// - `#[vertex(normalized)]` uses the `Unorm`/`Snorm` format for 8 and 16 bit integers
+/// - `#[vertex(format = "Float16x2")]` sets the `wgpu::VertexFormat` explicitly
+/// - `#[vertex(skip)]` leaves out the field, e.g. padding
+///
+/// Container attributes:
+/// - `#[vertex(instance)]` advances the buffer per instance instead of per vertex
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "Vertex can't be derived for generic types",
+        ));
+    }
+
+    if !has_repr_c(&input)? {
+        return Err(syn::Error::new(
+            ident.span(),
+            "Vertex can only be derived for #[repr(C)] structs, the field layout has to be stable",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            _ => {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut instance = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("instance") {
+                instance = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown vertex attribute, expected `instance`"))
+            }
+        })?;
+    }
+
+    let mut attributes = Vec::new();
+    let mut next_location = 0u32;
+    // Field that took each location so far
+    let mut used_locations = BTreeMap::new();
+
+    for field in fields {
+        let options = FieldOptions::parse(field)?;
+        if options.skip {
+            continue;
+        }
+
+        // Can't be None, only named fields get here
+        let name = field.ident.as_ref().unwrap_or_else(|| unreachable!());
+        let location = options.location.unwrap_or(next_location);
+
+        let (format, rows) = match &options.format {
+            Some(format) => (format.clone(), 1),
+            None => infer_format(&field.ty, options.normalized)?,
+        };
+
+        for row in 0..rows {
+            let shader_location = location + row;
+            if let Some(other) = used_locations.insert(shader_location, name) {
+                return Err(syn::Error::new(
+                    name.span(),
+                    format!("location {shader_location} is already taken by field `{other}`"),
+                ));
+            }
+            attributes.push(quote! {
+                ::renderer::__private::wgpu::VertexAttribute {
+                    format: ::renderer::__private::wgpu::VertexFormat::#format,
+                    offset: (::std::mem::offset_of!(#ident, #name)
+                        + #row as usize * ::renderer::__private::wgpu::VertexFormat::#format.size() as usize)
+                        as ::renderer::__private::wgpu::BufferAddress,
+                    shader_location: #shader_location,
+                }
+            });
+        }
+
+        next_location = location + rows;
+    }
+
+    let step_mode = if instance {
+        quote!(::renderer::__private::wgpu::VertexStepMode::Instance)
+    } else {
+        quote!(::renderer::__private::wgpu::VertexStepMode::Vertex)
+    };
+
+    let assert_pod = quote_spanned! {ident.span()=>
+        const _: fn() = || {
+            fn assert_pod<T: ::renderer::__private::bytemuck::Pod>() {}
+            assert_pod::<#ident>();
+        };
+    };
+
+    Ok(quote! {
+        #assert_pod
+
+        impl ::renderer::wgpu::Vertex for #ident {
+            fn desc<'a>() -> ::renderer::__private::wgpu::VertexBufferLayout<'a> {
+                const ATTRIBUTES: &[::renderer::__private::wgpu::VertexAttribute] = &[#(#attributes),*];
+
+                ::renderer::__private::wgpu::VertexBufferLayout {
+                    array_stride: ::std::mem::size_of::<#ident>() as ::renderer::__private::wgpu::BufferAddress,
+                    step_mode: #step_mode,
+                    attributes: ATTRIBUTES,
+                }
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    location: Option<u32>,
+    normalized: bool,
+    format: Option<syn::Ident>,
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut options = Self::default();
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("location") {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    options.location = Some(lit.base10_parse()?);
+                } else if meta.path.is_ident("normalized") {
+                    options.normalized = true;
+                } else if meta.path.is_ident("format") {
+                    options.format = Some(parse_format(meta.value()?.parse()?)?);
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error(
+                        "unknown vertex attribute, expected `location`, `normalized`, `format` or `skip`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// The `wgpu::VertexFormat` variant named by a `format = "..."` literal
+fn parse_format(lit: syn::LitStr) -> syn::Result<syn::Ident> {
+    let mut format = syn::parse_str::<syn::Ident>(&lit.value()).map_err(|_| {
+        syn::Error::new(
+            lit.span(),
+            "expected the name of a wgpu::VertexFormat, e.g. \"Float16x2\"",
+        )
+    })?;
+    format.set_span(lit.span());
+
+    Ok(format)
+}
+
+fn has_repr_c(input: &DeriveInput) -> syn::Result<bool> {
+    let mut repr_c = false;
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            }
+            // Skip arguments like the one of `align(16)`
+            if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<proc_macro2::TokenStream>()?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(repr_c)
+}
+
+/// Vertex format of a field and how many locations it takes
+fn infer_format(ty: &Type, normalized: bool) -> syn::Result<(syn::Ident, u32)> {
+    let unsupported = || {
+        syn::Error::new(
+            ty.span(),
+            "can't infer a vertex format for this type, set it with #[vertex(format = \"...\")]",
+        )
+    };
+
+    let (scalar, len, rows) = match ty {
+        Type::Path(_) => (scalar_name(ty).ok_or_else(unsupported)?, 1, 1),
+        Type::Array(array) => {
+            let len = array_len(&array.len).ok_or_else(unsupported)?;
+            match &*array.elem {
+                Type::Array(row) => (
+                    scalar_name(&row.elem).ok_or_else(unsupported)?,
+                    array_len(&row.len).ok_or_else(unsupported)?,
+                    len,
+                ),
+                elem => (scalar_name(elem).ok_or_else(unsupported)?, len, 1),
+            }
+        }
+        _ => return Err(unsupported()),
+    };
+
+    let base = match (scalar.as_str(), normalized) {
+        ("f32", false) => "Float32",
+        ("f64", false) => "Float64",
+        ("u32", false) => "Uint32",
+        ("i32", false) => "Sint32",
+        ("u16", false) => "Uint16",
+        ("i16", false) => "Sint16",
+        ("u8", false) => "Uint8",
+        ("i8", false) => "Sint8",
+        ("u16", true) => "Unorm16",
+        ("i16", true) => "Snorm16",
+        ("u8", true) => "Unorm8",
+        ("i8", true) => "Snorm8",
+        (_, true) => {
+            return Err(syn::Error::new(
+                ty.span(),
+                "only 8 and 16 bit integers can be normalized",
+            ))
+        }
+        _ => return Err(unsupported()),
+    };
+
+    let small = matches!(scalar.as_str(), "u16" | "i16" | "u8" | "i8");
+    let format = match (len, small) {
+        (1, false) => base.to_string(),
+        (2..=4, false) | (2 | 4, true) => format!("{base}x{len}"),
+        _ => {
+            return Err(syn::Error::new(
+                ty.span(),
+                format!("no vertex format with {len} components of {scalar}"),
+            ))
+        }
+    };
+
+    Ok((syn::Ident::new(&format, Span::call_site()), rows))
+}
+
+fn scalar_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            path.path.get_ident().map(|ident| ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn array_len(len: &syn::Expr) -> Option<u32> {
+    match len {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(lit),
+            ..
+        }) => lit.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The message of the error the derive reports for `input`
+    fn error(input: DeriveInput) -> String {
+        match expand(input) {
+            Ok(_) => panic!("expected the derive to fail"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn missing_repr_c_is_an_error() {
+        let message = error(syn::parse_quote! {
+            struct V {
+                position: [f32; 3],
+            }
+        });
+        assert!(message.contains("#[repr(C)]"), "{message}");
+    }
+
+    #[test]
+    fn unsupported_field_types_are_an_error() {
+        let message = error(syn::parse_quote! {
+            #[repr(C)]
+            struct V {
+                position: [f32; 3],
+                flag: bool,
+            }
+        });
+        assert!(message.contains("can't infer a vertex format"), "{message}");
+
+        let message = error(syn::parse_quote! {
+            #[repr(C)]
+            struct V {
+                position: [f32; 5],
+            }
+        });
+        assert_eq!(message, "no vertex format with 5 components of f32");
+    }
+
+    #[test]
+    fn formats_have_to_be_identifiers() {
+        for format in ["", "Float32 x2", "2Float"] {
+            let message = error(syn::parse_quote! {
+                #[repr(C)]
+                struct V {
+                    #[vertex(format = #format)]
+                    position: [u16; 2],
+                }
+            });
+            assert!(
+                message.starts_with("expected the name of a wgpu::VertexFormat"),
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_locations_are_an_error() {
+        let message = error(syn::parse_quote! {
+            #[repr(C)]
+            struct V {
+                position: [f32; 3],
+                uv: [f32; 2],
+                #[vertex(location = 1)]
+                color: [f32; 4],
+            }
+        });
+        assert_eq!(message, "location 1 is already taken by field `uv`");
+
+        // Matrix rows take a location each
+        let message = error(syn::parse_quote! {
+            #[repr(C)]
+            struct V {
+                #[vertex(location = 2)]
+                model: [[f32; 4]; 4],
+                #[vertex(location = 5)]
+                tint: [f32; 4],
+            }
+        });
+        assert_eq!(message, "location 5 is already taken by field `model`");
+    }
+
+    #[test]
+    fn explicit_locations_can_leave_gaps() {
+        let input = syn::parse_quote! {
+            #[repr(C)]
+            struct V {
+                #[vertex(location = 3)]
+                position: [f32; 3],
+                #[vertex(location = 0)]
+                uv: [f32; 2],
+                color: [f32; 4],
+            }
+        };
+        assert!(expand(input).is_ok());
+    }
+}
@@ -0,0 +1,626 @@
+use crate::CodegenError;
+use naga::{Handle, Type, TypeInner};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Rust module with the structs and binding constants of one shader
+///
+/// Empty if the shader has neither host-shareable structs nor bindings.
+pub(crate) fn module(module: &naga::Module, path: &Path) -> Result<String, CodegenError> {
+    let emitter = Emitter { module, path };
+
+    let mut structs = Vec::new();
+    for (_, var) in module.global_variables.iter() {
+        if matches!(
+            var.space,
+            naga::AddressSpace::Uniform
+                | naga::AddressSpace::Storage { .. }
+                | naga::AddressSpace::PushConstant
+        ) {
+            emitter.collect_structs(var.ty, &mut structs);
+        }
+    }
+
+    let mut items = Vec::new();
+    for ty in structs {
+        items.push(emitter.emit_struct(ty)?);
+    }
+    for (_, var) in module.global_variables.iter() {
+        if let Some(binding) = &var.binding {
+            items.push(emitter.emit_binding(var, binding)?);
+        }
+    }
+
+    if items.is_empty() {
+        return Ok(String::new());
+    }
+
+    Ok(format!(
+        "\n/// Types and bindings of `{}`\npub mod {} {{\n{}}}\n",
+        path.display(),
+        module_name(path),
+        items.join("\n")
+    ))
+}
+
+struct Emitter<'a> {
+    module: &'a naga::Module,
+    path: &'a Path,
+}
+
+/// A Rust type and its size in bytes
+struct RustType {
+    name: String,
+    size: u32,
+}
+
+impl<'a> Emitter<'a> {
+    /// Collects the structs reachable from `ty`, dependencies first
+    fn collect_structs(&self, ty: Handle<Type>, structs: &mut Vec<Handle<Type>>) {
+        match &self.module.types[ty].inner {
+            TypeInner::Struct { members, .. } => {
+                for member in members {
+                    self.collect_structs(member.ty, structs);
+                }
+                if !structs.contains(&ty) {
+                    structs.push(ty);
+                }
+            }
+            TypeInner::Array { base, .. } | TypeInner::BindingArray { base, .. } => {
+                self.collect_structs(*base, structs)
+            }
+            _ => {}
+        }
+    }
+
+    fn emit_struct(&self, ty: Handle<Type>) -> Result<String, CodegenError> {
+        let name = self.type_name(ty);
+        let TypeInner::Struct { members, span } = &self.module.types[ty].inner else {
+            unreachable!("only structs are collected")
+        };
+
+        // A runtime-sized array at the end can't be part of a Pod struct
+        let (members, tail) = match members.split_last() {
+            Some((last, head)) if self.is_runtime_array(last.ty) => (head, Some(last)),
+            _ => (members.as_slice(), None),
+        };
+        let size = tail.map_or(*span, |tail| tail.offset);
+
+        let mut fields = Vec::new();
+        let mut offset = 0;
+        let mut padding = 0;
+
+        for member in members {
+            if member.offset > offset {
+                fields.push(padding_field(&mut padding, member.offset - offset));
+            }
+
+            let rust = self.rust_type(member.ty)?;
+            let field = match &member.name {
+                Some(name) => field_name(name),
+                None => format!("field_{}", fields.len()),
+            };
+            fields.push(Field::Member {
+                name: field,
+                ty: rust.name,
+                offset: member.offset,
+            });
+            offset = member.offset + rust.size;
+        }
+        if size > offset {
+            fields.push(padding_field(&mut padding, size - offset));
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(out, "    /// `struct {name}`, {size} bytes");
+        if let Some(tail) = tail {
+            let _ = writeln!(out, "    ///");
+            let _ = writeln!(
+                out,
+                "    /// The runtime-sized array `{}` at the end is not part of the struct.",
+                tail.name.as_deref().unwrap_or("_")
+            );
+        }
+        let _ = writeln!(out, "    #[repr(C)]");
+        let _ = writeln!(
+            out,
+            "    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]"
+        );
+        let _ = writeln!(out, "    pub struct {name} {{");
+        for field in &fields {
+            let _ = match field {
+                Field::Member { name, ty, .. } => writeln!(out, "        pub {name}: {ty},"),
+                Field::Padding { name, len } => writeln!(out, "        {name}: [u8; {len}],"),
+            };
+        }
+        let _ = writeln!(out, "    }}\n");
+
+        let arguments = fields
+            .iter()
+            .filter_map(|field| match field {
+                Field::Member { name, ty, .. } => Some(format!("{name}: {ty}")),
+                Field::Padding { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(out, "    impl {name} {{");
+        let _ = writeln!(out, "        #[allow(clippy::too_many_arguments)]");
+        let _ = writeln!(
+            out,
+            "        pub const fn new({}) -> Self {{",
+            arguments.join(", ")
+        );
+        let _ = writeln!(out, "            Self {{");
+        for field in &fields {
+            let _ = match field {
+                Field::Member { name, .. } => writeln!(out, "                {name},"),
+                Field::Padding { name, len } => {
+                    writeln!(out, "                {name}: [0; {len}],")
+                }
+            };
+        }
+        let _ = writeln!(out, "            }}");
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}\n");
+
+        // Catches any mismatch with WGSL at compile time
+        let _ = writeln!(
+            out,
+            "    const _: () = assert!(::std::mem::size_of::<{name}>() == {size});"
+        );
+        for field in &fields {
+            if let Field::Member {
+                name: field,
+                offset,
+                ..
+            } = field
+            {
+                let _ = writeln!(
+                    out,
+                    "    const _: () = assert!(::std::mem::offset_of!({name}, {field}) == {offset});"
+                );
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn emit_binding(
+        &self,
+        var: &naga::GlobalVariable,
+        binding: &naga::ResourceBinding,
+    ) -> Result<String, CodegenError> {
+        let name = match &var.name {
+            Some(name) => name.to_uppercase(),
+            None => format!("BINDING_{}_{}", binding.group, binding.binding),
+        };
+
+        let space = match var.space {
+            naga::AddressSpace::Uniform => "<uniform>",
+            naga::AddressSpace::Storage { access }
+                if access.contains(naga::StorageAccess::STORE) =>
+            {
+                "<storage, read_write>"
+            }
+            naga::AddressSpace::Storage { .. } => "<storage>",
+            _ => "",
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "    /// `@group({}) @binding({}) var{space} {}: {}`",
+            binding.group,
+            binding.binding,
+            var.name.as_deref().unwrap_or("_"),
+            self.wgsl_name(var.ty)
+        );
+        let _ = writeln!(out, "    pub const {name}_GROUP: u32 = {};", binding.group);
+        let _ = writeln!(
+            out,
+            "    pub const {name}_BINDING: u32 = {};",
+            binding.binding
+        );
+
+        let buffer = matches!(
+            var.space,
+            naga::AddressSpace::Uniform | naga::AddressSpace::Storage { .. }
+        );
+        if buffer && !self.is_runtime_sized(var.ty) {
+            let size = self.rust_type(var.ty)?.size;
+            let _ = writeln!(out, "    pub const {name}_SIZE: u64 = {size};");
+        }
+
+        Ok(out)
+    }
+
+    fn rust_type(&self, ty: Handle<Type>) -> Result<RustType, CodegenError> {
+        let unsupported = |reason| CodegenError::Unsupported {
+            path: self.path.to_path_buf(),
+            ty: self.wgsl_name(ty),
+            reason,
+        };
+
+        match &self.module.types[ty].inner {
+            TypeInner::Scalar { kind, width } | TypeInner::Atomic { kind, width } => {
+                let name = scalar_name(*kind, *width)
+                    .ok_or_else(|| unsupported("not a host-shareable scalar"))?;
+                Ok(RustType {
+                    name: name.to_string(),
+                    size: *width as u32,
+                })
+            }
+            TypeInner::Vector { size, kind, width } => {
+                let name = scalar_name(*kind, *width)
+                    .ok_or_else(|| unsupported("not a host-shareable scalar"))?;
+                let len = *size as u32;
+                Ok(RustType {
+                    name: format!("[{name}; {len}]"),
+                    size: len * *width as u32,
+                })
+            }
+            TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            } => {
+                let name = scalar_name(naga::ScalarKind::Float, *width)
+                    .ok_or_else(|| unsupported("not a host-shareable scalar"))?;
+                // Columns are aligned like vectors, so three rows take the space of four
+                let rows = match rows {
+                    naga::VectorSize::Tri => 4,
+                    rows => *rows as u32,
+                };
+                let columns = *columns as u32;
+                Ok(RustType {
+                    name: format!("[[{name}; {rows}]; {columns}]"),
+                    size: columns * rows * *width as u32,
+                })
+            }
+            TypeInner::Array { base, size, stride } => {
+                let naga::ArraySize::Constant(len) = size else {
+                    return Err(unsupported(
+                        "runtime-sized arrays only work at the end of a struct",
+                    ));
+                };
+                let len = array_len(&self.module.constants[*len].inner)
+                    .ok_or_else(|| unsupported("the array length is not a plain integer"))?;
+
+                let element = self.rust_type(*base)?;
+                let element = match &self.module.types[*base].inner {
+                    _ if element.size == *stride => element.name,
+                    // Elements of `array<vec3<f32>, N>` are padded to four components
+                    TypeInner::Vector {
+                        size: naga::VectorSize::Tri,
+                        kind,
+                        width,
+                    } if *stride == 4 * *width as u32 => {
+                        // Can't be None, the vector itself was accepted above
+                        let name = scalar_name(*kind, *width).unwrap_or_else(|| unreachable!());
+                        format!("[{name}; 4]")
+                    }
+                    _ => {
+                        return Err(unsupported(
+                            "the array stride doesn't match the element size",
+                        ))
+                    }
+                };
+
+                Ok(RustType {
+                    name: format!("[{element}; {len}]"),
+                    size: len * stride,
+                })
+            }
+            TypeInner::Struct { span, .. } => Ok(RustType {
+                name: self.type_name(ty),
+                size: *span,
+            }),
+            _ => Err(unsupported("not a host-shareable type")),
+        }
+    }
+
+    fn is_runtime_array(&self, ty: Handle<Type>) -> bool {
+        matches!(
+            self.module.types[ty].inner,
+            TypeInner::Array {
+                size: naga::ArraySize::Dynamic,
+                ..
+            }
+        )
+    }
+
+    /// Runtime-sized arrays and structs ending in one
+    fn is_runtime_sized(&self, ty: Handle<Type>) -> bool {
+        match &self.module.types[ty].inner {
+            TypeInner::Struct { members, .. } => members
+                .last()
+                .is_some_and(|last| self.is_runtime_sized(last.ty)),
+            _ => self.is_runtime_array(ty),
+        }
+    }
+
+    fn type_name(&self, ty: Handle<Type>) -> String {
+        match &self.module.types[ty].name {
+            Some(name) => name.clone(),
+            None => format!("Type{}", ty.index()),
+        }
+    }
+
+    /// Name of the type as written in WGSL, for docs and errors
+    fn wgsl_name(&self, ty: Handle<Type>) -> String {
+        let inner = &self.module.types[ty].inner;
+        if let Some(name) = &self.module.types[ty].name {
+            return name.clone();
+        }
+
+        match inner {
+            TypeInner::Scalar { kind, width } | TypeInner::Atomic { kind, width } => {
+                let name = wgsl_scalar_name(*kind, *width);
+                match inner {
+                    TypeInner::Atomic { .. } => format!("atomic<{name}>"),
+                    _ => name.to_string(),
+                }
+            }
+            TypeInner::Vector { size, kind, width } => {
+                format!("vec{}<{}>", *size as u32, wgsl_scalar_name(*kind, *width))
+            }
+            TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            } => format!(
+                "mat{}x{}<{}>",
+                *columns as u32,
+                *rows as u32,
+                wgsl_scalar_name(naga::ScalarKind::Float, *width)
+            ),
+            TypeInner::Array { base, size, .. } => match size {
+                naga::ArraySize::Constant(len) => {
+                    match array_len(&self.module.constants[*len].inner) {
+                        Some(len) => format!("array<{}, {len}>", self.wgsl_name(*base)),
+                        None => format!("array<{}, _>", self.wgsl_name(*base)),
+                    }
+                }
+                naga::ArraySize::Dynamic => format!("array<{}>", self.wgsl_name(*base)),
+            },
+            TypeInner::BindingArray { base, .. } => {
+                format!("binding_array<{}>", self.wgsl_name(*base))
+            }
+            TypeInner::Sampler { comparison: true } => "sampler_comparison".to_string(),
+            TypeInner::Sampler { comparison: false } => "sampler".to_string(),
+            TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            } => {
+                let dim = match dim {
+                    naga::ImageDimension::D1 => "1d",
+                    naga::ImageDimension::D2 => "2d",
+                    naga::ImageDimension::D3 => "3d",
+                    naga::ImageDimension::Cube => "cube",
+                };
+                let array = if *arrayed { "_array" } else { "" };
+                match class {
+                    naga::ImageClass::Sampled { kind, multi } => {
+                        let multi = if *multi { "multisampled_" } else { "" };
+                        format!(
+                            "texture_{multi}{dim}{array}<{}>",
+                            wgsl_scalar_name(*kind, 4)
+                        )
+                    }
+                    naga::ImageClass::Depth { multi } => {
+                        let multi = if *multi { "multisampled_" } else { "" };
+                        format!("texture_depth_{multi}{dim}{array}")
+                    }
+                    naga::ImageClass::Storage { format, access } => {
+                        let access = match (
+                            access.contains(naga::StorageAccess::LOAD),
+                            access.contains(naga::StorageAccess::STORE),
+                        ) {
+                            (true, true) => "read_write",
+                            (true, false) => "read",
+                            (false, _) => "write",
+                        };
+                        format!(
+                            "texture_storage_{dim}{array}<{}, {access}>",
+                            wgsl_storage_format_name(*format)
+                        )
+                    }
+                }
+            }
+            _ => "_".to_string(),
+        }
+    }
+}
+
+enum Field {
+    Member {
+        name: String,
+        ty: String,
+        offset: u32,
+    },
+    Padding {
+        name: String,
+        len: u32,
+    },
+}
+
+fn padding_field(padding: &mut u32, len: u32) -> Field {
+    let field = Field::Padding {
+        name: format!("_pad{padding}"),
+        len,
+    };
+    *padding += 1;
+    field
+}
+
+fn scalar_name(kind: naga::ScalarKind, width: naga::Bytes) -> Option<&'static str> {
+    match (kind, width) {
+        (naga::ScalarKind::Float, 4) => Some("f32"),
+        (naga::ScalarKind::Float, 8) => Some("f64"),
+        (naga::ScalarKind::Sint, 4) => Some("i32"),
+        (naga::ScalarKind::Uint, 4) => Some("u32"),
+        _ => None,
+    }
+}
+
+fn wgsl_scalar_name(kind: naga::ScalarKind, width: naga::Bytes) -> &'static str {
+    match (kind, width) {
+        (naga::ScalarKind::Float, 2) => "f16",
+        (naga::ScalarKind::Float, 8) => "f64",
+        (naga::ScalarKind::Float, _) => "f32",
+        (naga::ScalarKind::Sint, _) => "i32",
+        (naga::ScalarKind::Uint, _) => "u32",
+        (naga::ScalarKind::Bool, _) => "bool",
+    }
+}
+
+fn wgsl_storage_format_name(format: naga::StorageFormat) -> &'static str {
+    use naga::StorageFormat as Sf;
+
+    match format {
+        Sf::R8Unorm => "r8unorm",
+        Sf::R8Snorm => "r8snorm",
+        Sf::R8Uint => "r8uint",
+        Sf::R8Sint => "r8sint",
+        Sf::R16Uint => "r16uint",
+        Sf::R16Sint => "r16sint",
+        Sf::R16Float => "r16float",
+        Sf::Rg8Unorm => "rg8unorm",
+        Sf::Rg8Snorm => "rg8snorm",
+        Sf::Rg8Uint => "rg8uint",
+        Sf::Rg8Sint => "rg8sint",
+        Sf::R32Uint => "r32uint",
+        Sf::R32Sint => "r32sint",
+        Sf::R32Float => "r32float",
+        Sf::Rg16Uint => "rg16uint",
+        Sf::Rg16Sint => "rg16sint",
+        Sf::Rg16Float => "rg16float",
+        Sf::Rgba8Unorm => "rgba8unorm",
+        Sf::Rgba8Snorm => "rgba8snorm",
+        Sf::Rgba8Uint => "rgba8uint",
+        Sf::Rgba8Sint => "rgba8sint",
+        Sf::Rgb10a2Unorm => "rgb10a2unorm",
+        Sf::Rg11b10Float => "rg11b10float",
+        Sf::Rg32Uint => "rg32uint",
+        Sf::Rg32Sint => "rg32sint",
+        Sf::Rg32Float => "rg32float",
+        Sf::Rgba16Uint => "rgba16uint",
+        Sf::Rgba16Sint => "rgba16sint",
+        Sf::Rgba16Float => "rgba16float",
+        Sf::Rgba32Uint => "rgba32uint",
+        Sf::Rgba32Sint => "rgba32sint",
+        Sf::Rgba32Float => "rgba32float",
+        Sf::R16Unorm => "r16unorm",
+        Sf::R16Snorm => "r16snorm",
+        Sf::Rg16Unorm => "rg16unorm",
+        Sf::Rg16Snorm => "rg16snorm",
+        Sf::Rgba16Unorm => "rgba16unorm",
+        Sf::Rgba16Snorm => "rgba16snorm",
+    }
+}
+
+fn array_len(constant: &naga::ConstantInner) -> Option<u32> {
+    match *constant {
+        naga::ConstantInner::Scalar {
+            value: naga::ScalarValue::Uint(len),
+            ..
+        } => u32::try_from(len).ok(),
+        naga::ConstantInner::Scalar {
+            value: naga::ScalarValue::Sint(len),
+            ..
+        } => u32::try_from(len).ok(),
+        _ => None,
+    }
+}
+
+/// `post/blur.wgsl` becomes `post_blur`
+fn module_name(path: &Path) -> String {
+    let name = path
+        .with_extension("")
+        .to_string_lossy()
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect::<String>();
+
+    field_name(&name)
+}
+
+/// Escapes names that aren't valid Rust identifiers as they are
+fn field_name(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else",
+        "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+        "move", "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "type",
+        "unsafe", "use", "where", "while", "yield",
+    ];
+
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Emits code for `source` without validating it, so types that validation rejects get through
+    fn emit(source: &str) -> Result<String, CodegenError> {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        super::module(&module, Path::new("bad.wgsl"))
+    }
+
+    fn unsupported(result: Result<String, CodegenError>) -> (String, &'static str) {
+        match result {
+            Err(CodegenError::Unsupported { path, ty, reason }) => {
+                assert_eq!(path, Path::new("bad.wgsl"));
+                (ty, reason)
+            }
+            Err(e) => panic!("expected an unsupported type, got {e}"),
+            Ok(code) => panic!("expected an unsupported type, got\n{code}"),
+        }
+    }
+
+    #[test]
+    fn non_host_shareable_scalars_are_unsupported() {
+        let source = "
+            struct Flags { enabled: bool }
+            @group(0) @binding(0) var<storage> flags: Flags;
+        ";
+        assert_eq!(
+            unsupported(emit(source)),
+            ("bool".to_string(), "not a host-shareable scalar")
+        );
+    }
+
+    #[test]
+    fn runtime_sized_arrays_before_the_end_are_unsupported() {
+        let source = "
+            struct Data { values: array<u32>, count: u32 }
+            @group(0) @binding(0) var<storage> data: Data;
+        ";
+        assert_eq!(
+            unsupported(emit(source)),
+            (
+                "array<u32>".to_string(),
+                "runtime-sized arrays only work at the end of a struct"
+            )
+        );
+    }
+
+    #[test]
+    fn storage_textures_are_named_like_in_wgsl() {
+        let source = "
+            @group(0) @binding(0) var depth: texture_storage_2d_array<r32float, write>;
+        ";
+        assert!(emit(source).unwrap().contains(
+            "/// `@group(0) @binding(0) var depth: texture_storage_2d_array<r32float, write>`"
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! Generates Rust types for the host-shareable structs of WGSL shaders
+//!
+//! Meant to run in the `main` of a build script, e.g.
+//!
+//! ```no_run
+//! shader_codegen::Generator::new("../resources/shaders")
+//!     .write_to_out_dir("shaders.rs")
+//!     .unwrap_or_else(|e| panic!("{}", e.report()));
+//! ```
+//!
+//! and including the output with
+//! `include!(concat!(env!("OUT_DIR"), "/shaders.rs"));`.
+//! The including crate needs `bytemuck` with the `derive` and
+//! `min_const_generics` features, the latter for arrays and padding of any length.
+
+mod emit;
+
+use renderer::wgpu::{Preprocessor, Shader, ShaderError};
+use std::path::{Path, PathBuf};
+
+/// Generates a Rust module per WGSL file below a shader root
+///
+/// Each module holds a `#[repr(C)]`, `Pod` struct for every WGSL struct used
+/// in a uniform, storage or push constant buffer, with explicit padding fields
+/// so the layout matches WGSL's alignment rules. Padding is private, the
+/// structs are created with their `new` function or `bytemuck::Zeroable`.
+///
+/// For every resource binding there are `<NAME>_GROUP` and `<NAME>_BINDING`
+/// constants, buffers of a fixed size also get `<NAME>_SIZE`, e.g. for
+/// [`Buffer::new`](renderer::wgpu::Buffer::new).
+///
+/// Shaders go through the [`Preprocessor`] without defines, so `#ifdef`
+/// blocks are only seen as in their default variant.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    preprocessor: Preprocessor,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CodegenError {
+    #[error(transparent)]
+    Shader(#[from] ShaderError),
+    #[error("failed to access {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("can't generate a Rust type for `{ty}` in {}: {reason}", path.display())]
+    Unsupported {
+        path: PathBuf,
+        ty: String,
+        reason: &'static str,
+    },
+    #[error("OUT_DIR is not set, the generator has to run in a build script")]
+    NoOutDir,
+}
+
+impl CodegenError {
+    /// The message with the highlighted source lines for shader errors
+    pub fn report(&self) -> String {
+        match self {
+            Self::Shader(err) => match err.diagnostic() {
+                Some(diagnostic) => diagnostic.render(),
+                None => err.to_string(),
+            },
+            err => err.to_string(),
+        }
+    }
+}
+
+impl Generator {
+    /// Generates code for all `.wgsl` files below `root`, including subdirectories
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_preprocessor(Preprocessor::new(root))
+    }
+
+    /// Uses the preprocessor's root and defines
+    pub fn with_preprocessor(preprocessor: Preprocessor) -> Self {
+        Self { preprocessor }
+    }
+
+    /// Rust source with one module per shader, named after its path relative to the root
+    ///
+    /// Shaders without host-shareable structs or bindings, e.g. files that only
+    /// hold functions for others to include, don't get a module.
+    pub fn generate(&self) -> Result<String, CodegenError> {
+        let root = self.preprocessor.root();
+        let mut paths = Vec::new();
+        collect_wgsl(root, Path::new(""), &mut paths)?;
+        paths.sort();
+
+        let mut out = format!(
+            "// Generated by shader_codegen from {}, do not edit\n",
+            root.display()
+        );
+
+        for path in &paths {
+            // Validates first, parse and validation errors come with a diagnostic then
+            Shader::validate_file(&self.preprocessor, path)?;
+            let source = self.preprocessor.process_file(path)?;
+            // Can't crash, the source was validated above
+            let module =
+                naga::front::wgsl::parse_str(source.code()).unwrap_or_else(|_| unreachable!());
+
+            out.push_str(&emit::module(&module, path)?);
+        }
+
+        Ok(out)
+    }
+
+    /// Writes the generated code to `file_name` in `OUT_DIR` and makes cargo
+    /// rerun the build script when a shader changes
+    pub fn write_to_out_dir(&self, file_name: impl AsRef<Path>) -> Result<PathBuf, CodegenError> {
+        let out_dir = std::env::var_os("OUT_DIR").ok_or(CodegenError::NoOutDir)?;
+        let path = Path::new(&out_dir).join(file_name);
+
+        println!(
+            "cargo:rerun-if-changed={}",
+            self.preprocessor.root().display()
+        );
+
+        let code = self.generate()?;
+        std::fs::write(&path, code).map_err(|source| CodegenError::Io {
+            path: path.clone(),
+            source,
+        })?;
+
+        Ok(path)
+    }
+}
+
+/// Collects all `.wgsl` files below `root.join(dir)`, relative to `root`
+fn collect_wgsl(root: &Path, dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), CodegenError> {
+    let io_error = |source| CodegenError::Io {
+        path: root.join(dir),
+        source,
+    };
+
+    for entry in std::fs::read_dir(root.join(dir)).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let path = dir.join(entry.file_name());
+
+        if entry.file_type().map_err(io_error)?.is_dir() {
+            collect_wgsl(root, &path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "wgsl") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
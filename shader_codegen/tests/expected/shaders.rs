@@ -0,0 +1,205 @@
+// Generated by shader_codegen from tests/shaders, do not edit
+
+/// Types and bindings of `lights.wgsl`
+pub mod lights {
+    /// `struct Light`, 32 bytes
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct Light {
+        pub position: [f32; 3],
+        pub intensity: f32,
+        pub color: [f32; 3],
+        _pad0: [u8; 4],
+    }
+
+    impl Light {
+        #[allow(clippy::too_many_arguments)]
+        pub const fn new(position: [f32; 3], intensity: f32, color: [f32; 3]) -> Self {
+            Self {
+                position,
+                intensity,
+                color,
+                _pad0: [0; 4],
+            }
+        }
+    }
+
+    const _: () = assert!(::std::mem::size_of::<Light>() == 32);
+    const _: () = assert!(::std::mem::offset_of!(Light, position) == 0);
+    const _: () = assert!(::std::mem::offset_of!(Light, intensity) == 12);
+    const _: () = assert!(::std::mem::offset_of!(Light, color) == 16);
+
+    /// `struct Lights`, 1536 bytes
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct Lights {
+        pub count: u32,
+        _pad0: [u8; 60],
+        pub exposure: f32,
+        _pad1: [u8; 12],
+        pub lights: [Light; 40],
+        pub weights: [f32; 40],
+        _pad2: [u8; 16],
+    }
+
+    impl Lights {
+        #[allow(clippy::too_many_arguments)]
+        pub const fn new(count: u32, exposure: f32, lights: [Light; 40], weights: [f32; 40]) -> Self {
+            Self {
+                count,
+                _pad0: [0; 60],
+                exposure,
+                _pad1: [0; 12],
+                lights,
+                weights,
+                _pad2: [0; 16],
+            }
+        }
+    }
+
+    const _: () = assert!(::std::mem::size_of::<Lights>() == 1536);
+    const _: () = assert!(::std::mem::offset_of!(Lights, count) == 0);
+    const _: () = assert!(::std::mem::offset_of!(Lights, exposure) == 64);
+    const _: () = assert!(::std::mem::offset_of!(Lights, lights) == 80);
+    const _: () = assert!(::std::mem::offset_of!(Lights, weights) == 1360);
+
+    /// `@group(0) @binding(0) var<storage> lights: Lights`
+    pub const LIGHTS_GROUP: u32 = 0;
+    pub const LIGHTS_BINDING: u32 = 0;
+    pub const LIGHTS_SIZE: u64 = 1536;
+}
+
+/// Types and bindings of `particles.wgsl`
+pub mod particles {
+    /// `struct Camera`, 160 bytes
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct Camera {
+        pub view: [[f32; 4]; 4],
+        pub normal: [[f32; 4]; 3],
+        pub uv_transform: [[f32; 4]; 2],
+        pub position: [f32; 3],
+        _pad0: [u8; 4],
+    }
+
+    impl Camera {
+        #[allow(clippy::too_many_arguments)]
+        pub const fn new(view: [[f32; 4]; 4], normal: [[f32; 4]; 3], uv_transform: [[f32; 4]; 2], position: [f32; 3]) -> Self {
+            Self {
+                view,
+                normal,
+                uv_transform,
+                position,
+                _pad0: [0; 4],
+            }
+        }
+    }
+
+    const _: () = assert!(::std::mem::size_of::<Camera>() == 160);
+    const _: () = assert!(::std::mem::offset_of!(Camera, view) == 0);
+    const _: () = assert!(::std::mem::offset_of!(Camera, normal) == 64);
+    const _: () = assert!(::std::mem::offset_of!(Camera, uv_transform) == 112);
+    const _: () = assert!(::std::mem::offset_of!(Camera, position) == 144);
+
+    /// `struct Particle`, 32 bytes
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct Particle {
+        pub position: [f32; 3],
+        pub age: f32,
+        pub velocity: [f32; 3],
+        _pad0: [u8; 4],
+    }
+
+    impl Particle {
+        #[allow(clippy::too_many_arguments)]
+        pub const fn new(position: [f32; 3], age: f32, velocity: [f32; 3]) -> Self {
+            Self {
+                position,
+                age,
+                velocity,
+                _pad0: [0; 4],
+            }
+        }
+    }
+
+    const _: () = assert!(::std::mem::size_of::<Particle>() == 32);
+    const _: () = assert!(::std::mem::offset_of!(Particle, position) == 0);
+    const _: () = assert!(::std::mem::offset_of!(Particle, age) == 12);
+    const _: () = assert!(::std::mem::offset_of!(Particle, velocity) == 16);
+
+    /// `struct Particles`, 16 bytes
+    ///
+    /// The runtime-sized array `particles` at the end is not part of the struct.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct Particles {
+        pub count: u32,
+        _pad0: [u8; 12],
+    }
+
+    impl Particles {
+        #[allow(clippy::too_many_arguments)]
+        pub const fn new(count: u32) -> Self {
+            Self {
+                count,
+                _pad0: [0; 12],
+            }
+        }
+    }
+
+    const _: () = assert!(::std::mem::size_of::<Particles>() == 16);
+    const _: () = assert!(::std::mem::offset_of!(Particles, count) == 0);
+
+    /// `@group(0) @binding(0) var<uniform> camera: Camera`
+    pub const CAMERA_GROUP: u32 = 0;
+    pub const CAMERA_BINDING: u32 = 0;
+    pub const CAMERA_SIZE: u64 = 160;
+
+    /// `@group(0) @binding(1) var<storage, read_write> particles: Particles`
+    pub const PARTICLES_GROUP: u32 = 0;
+    pub const PARTICLES_BINDING: u32 = 1;
+}
+
+/// Types and bindings of `post/blur.wgsl`
+pub mod post_blur {
+    /// `struct BlurParams`, 80 bytes
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct BlurParams {
+        pub weights: [[f32; 4]; 4],
+        pub direction: [f32; 2],
+        pub radius: u32,
+        _pad0: [u8; 4],
+    }
+
+    impl BlurParams {
+        #[allow(clippy::too_many_arguments)]
+        pub const fn new(weights: [[f32; 4]; 4], direction: [f32; 2], radius: u32) -> Self {
+            Self {
+                weights,
+                direction,
+                radius,
+                _pad0: [0; 4],
+            }
+        }
+    }
+
+    const _: () = assert!(::std::mem::size_of::<BlurParams>() == 80);
+    const _: () = assert!(::std::mem::offset_of!(BlurParams, weights) == 0);
+    const _: () = assert!(::std::mem::offset_of!(BlurParams, direction) == 64);
+    const _: () = assert!(::std::mem::offset_of!(BlurParams, radius) == 72);
+
+    /// `@group(0) @binding(0) var<uniform> params: BlurParams`
+    pub const PARAMS_GROUP: u32 = 0;
+    pub const PARAMS_BINDING: u32 = 0;
+    pub const PARAMS_SIZE: u64 = 80;
+
+    /// `@group(0) @binding(1) var source: texture_2d<f32>`
+    pub const SOURCE_GROUP: u32 = 0;
+    pub const SOURCE_BINDING: u32 = 1;
+
+    /// `@group(0) @binding(2) var output: texture_storage_2d<rgba8unorm, write>`
+    pub const OUTPUT_GROUP: u32 = 0;
+    pub const OUTPUT_BINDING: u32 = 2;
+}
@@ -0,0 +1,81 @@
+use shader_codegen::Generator;
+
+/// The expected output, compiled as part of this test
+// Not every generated constant is used by the tests
+#[allow(dead_code)]
+mod shaders {
+    include!("expected/shaders.rs");
+}
+
+use shaders::lights::{Light, Lights, LIGHTS_BINDING, LIGHTS_GROUP, LIGHTS_SIZE};
+use shaders::particles::{Camera, Particle, Particles, CAMERA_SIZE, PARTICLES_BINDING};
+use shaders::post_blur::{BlurParams, OUTPUT_BINDING, OUTPUT_GROUP, PARAMS_SIZE};
+
+const EXPECTED: &str = "tests/expected/shaders.rs";
+
+#[test]
+fn output_matches_expected() {
+    let code = Generator::new("tests/shaders")
+        .generate()
+        .unwrap_or_else(|e| panic!("failed to generate shader types\n{}", e.report()));
+
+    if std::env::var_os("CODEGEN_UPDATE").is_some() {
+        std::fs::write(EXPECTED, &code).unwrap();
+        return;
+    }
+
+    assert_eq!(
+        code,
+        std::fs::read_to_string(EXPECTED).unwrap(),
+        "generated code changed, rerun with CODEGEN_UPDATE=1 to update {EXPECTED}"
+    );
+}
+
+#[test]
+fn long_arrays_and_padding_are_pod() {
+    let light = Light::new([1.0, 2.0, 3.0], 4.0, [0.5, 0.25, 1.0]);
+    let lights = Lights::new(3, 1.5, [light; 40], [0.5; 40]);
+
+    let bytes = bytemuck::bytes_of(&lights);
+    assert_eq!((LIGHTS_GROUP, LIGHTS_BINDING), (0, 0));
+    assert_eq!(bytes.len() as u64, LIGHTS_SIZE);
+    assert_eq!(bytes[64..68], 1.5f32.to_ne_bytes());
+    assert_eq!(bytes[80 + 39 * 32 + 16..][..4], 0.5f32.to_ne_bytes());
+    assert_eq!(*bytemuck::from_bytes::<Lights>(bytes), lights);
+}
+
+#[test]
+fn vec3_array_elements_take_four_floats() {
+    let params = BlurParams::new([[1.0, 2.0, 3.0, 0.0]; 4], [0.0, 1.0], 2);
+
+    let bytes = bytemuck::bytes_of(&params);
+    assert_eq!(bytes.len() as u64, PARAMS_SIZE);
+    assert_eq!(bytes[16..20], 1.0f32.to_ne_bytes());
+    assert_eq!(bytes[68..72], 1.0f32.to_ne_bytes());
+    assert_eq!((OUTPUT_GROUP, OUTPUT_BINDING), (0, 2));
+}
+
+#[test]
+fn matrix_columns_with_three_rows_are_padded() {
+    let mut camera = Camera::new([[0.0; 4]; 4], [[0.0; 4]; 3], [[0.0; 4]; 2], [1.0; 3]);
+    camera.normal[1][0] = 2.0;
+    camera.uv_transform[1][2] = 3.0;
+
+    let bytes = bytemuck::bytes_of(&camera);
+    assert_eq!(bytes.len() as u64, CAMERA_SIZE);
+    assert_eq!(bytes[64 + 16..][..4], 2.0f32.to_ne_bytes());
+    assert_eq!(bytes[112 + 16 + 8..][..4], 3.0f32.to_ne_bytes());
+    assert_eq!(bytes[144..148], 1.0f32.to_ne_bytes());
+}
+
+#[test]
+fn runtime_sized_array_follows_the_struct() {
+    let particle = Particle::new([1.0; 3], 0.5, [2.0; 3]);
+
+    // The buffer holds the head of the struct, then the particles at their offset
+    let mut bytes = bytemuck::bytes_of(&Particles::new(2)).to_vec();
+    bytes.extend_from_slice(bytemuck::cast_slice(&[particle; 2]));
+    assert_eq!(PARTICLES_BINDING, 1);
+    assert_eq!(bytes.len(), 16 + 2 * 32);
+    assert_eq!(bytes[16 + 32 + 12..][..4], 0.5f32.to_ne_bytes());
+}
@@ -0,0 +1,22 @@
+// Arrays and padding longer than bytemuck implements Pod for without const generics
+
+struct Light {
+    position: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+}
+
+struct Lights {
+    count: u32,
+    @align(64) exposure: f32,
+    lights: array<Light, 40>,
+    weights: array<f32, 40>,
+}
+
+@group(0) @binding(0)
+var<storage, read> lights: Lights;
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(lights.lights[0].color * lights.exposure, 1.0);
+}
@@ -0,0 +1,34 @@
+// Matrices with three rows and a runtime-sized array at the end of a struct
+
+struct Camera {
+    view: mat4x4<f32>,
+    normal: mat3x3<f32>,
+    uv_transform: mat2x3<f32>,
+    position: vec3<f32>,
+}
+
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+}
+
+struct Particles {
+    count: u32,
+    particles: array<Particle>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(0) @binding(1)
+var<storage, read_write> particles: Particles;
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= particles.count {
+        return;
+    }
+    let particle = particles.particles[id.x];
+    let moved = camera.view * vec4<f32>(particle.position + camera.normal * particle.velocity, 1.0);
+    particles.particles[id.x].position = moved.xyz + camera.uv_transform * vec2<f32>(particle.age) - camera.position;
+}
@@ -0,0 +1,21 @@
+// Arrays of vec3 and storage textures, in a subdirectory
+
+struct BlurParams {
+    weights: array<vec3<f32>, 4>,
+    direction: vec2<f32>,
+    radius: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: BlurParams;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+@group(0) @binding(2)
+var output: texture_storage_2d<rgba8unorm, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let texel = vec2<i32>(id.xy) + vec2<i32>(params.direction) * i32(params.radius);
+    let color = textureLoad(source, texel, 0);
+    textureStore(output, vec2<i32>(id.xy), vec4<f32>(color.rgb * params.weights[0], color.a));
+}
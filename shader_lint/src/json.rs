@@ -0,0 +1,182 @@
+use std::fmt::Write;
+
+/// Just enough JSON to dump reflection data
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys keep their insertion order
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    pub fn string(value: impl Into<String>) -> Self {
+        Self::String(value.into())
+    }
+
+    pub fn array<T>(values: impl IntoIterator<Item = T>, f: impl FnMut(T) -> Json) -> Self {
+        Self::Array(values.into_iter().map(f).collect())
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        let pad = "  ".repeat(indent + 1);
+
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(value) => {
+                let _ = write!(out, "{value}");
+            }
+            Self::Number(value) => {
+                let _ = write!(out, "{value}");
+            }
+            Self::String(value) => write_string(out, value),
+            Self::Array(values) if values.is_empty() => out.push_str("[]"),
+            Self::Array(values) => {
+                out.push_str("[\n");
+                for (i, value) in values.iter().enumerate() {
+                    out.push_str(&pad);
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < values.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push(']');
+            }
+            Self::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Self::Object(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    out.push_str(&pad);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push('}');
+            }
+        }
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        f.write_str(&out)
+    }
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_escaped() {
+        let json = Json::string("\"quoted\" C:\\path\nnext\r\tend \u{1} ä");
+        assert_eq!(
+            json.to_string(),
+            r#""\"quoted\" C:\\path\nnext\r\tend \u0001 ä""#
+        );
+    }
+
+    #[test]
+    fn scalars_and_empty_containers_stay_on_one_line() {
+        let json = Json::Array(vec![
+            Json::Null,
+            true.into(),
+            7u32.into(),
+            Json::from(None::<u64>),
+            Json::Array(Vec::new()),
+            Json::Object(Vec::new()),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            "[\n  null,\n  true,\n  7,\n  null,\n  [],\n  {}\n]"
+        );
+    }
+
+    #[test]
+    fn nested_values_are_indented() {
+        let json = Json::Object(vec![
+            ("name", Json::string("lit")),
+            (
+                "bindings",
+                Json::array([(0u32, 1u32), (1, 0)], |(group, binding)| {
+                    Json::Object(vec![("group", group.into()), ("binding", binding.into())])
+                }),
+            ),
+            ("nested", Json::Array(vec![Json::Array(vec![Json::Null])])),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            r#"{
+  "name": "lit",
+  "bindings": [
+    {
+      "group": 0,
+      "binding": 1
+    },
+    {
+      "group": 1,
+      "binding": 0
+    }
+  ],
+  "nested": [
+    [
+      null
+    ]
+  ]
+}"#
+        );
+    }
+}
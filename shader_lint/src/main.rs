@@ -0,0 +1,224 @@
+mod json;
+
+use json::Json;
+use renderer::wgpu::{Preprocessor, Shader, ShaderError, ShaderReflection, ShaderStage};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: shader_lint [--json] [SHADER_DIR]
+
+Parses and validates every shader below SHADER_DIR, resources/shaders by default,
+and stops at the first error. WGSL is preprocessed with SHADER_DIR as include root.
+
+  --json    print the reflection of all shaders as JSON";
+
+const DEFAULT_ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../resources/shaders");
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut root = None;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ if arg.starts_with('-') || root.is_some() => {
+                eprintln!("{USAGE}");
+                return ExitCode::from(2);
+            }
+            _ => root = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let root = root.unwrap_or_else(|| PathBuf::from(DEFAULT_ROOT));
+    let mut paths = Vec::new();
+    if let Err(e) = collect_files(&root, Path::new(""), &mut paths) {
+        eprintln!("error: failed to read {}: {e}", root.display());
+        return ExitCode::FAILURE;
+    }
+    paths.sort();
+
+    let preprocessor = Preprocessor::new(&root);
+    let mut shaders = Vec::new();
+
+    for path in &paths {
+        match Shader::validate_file(&preprocessor, path) {
+            Ok(reflection) => shaders.push(shader_json(path, &reflection)),
+            // Not a shader, e.g. a readme next to them
+            Err(ShaderError::UnknownLanguage { .. }) => {}
+            Err(e) => {
+                match e.diagnostic() {
+                    Some(diagnostic) => eprint!("{}", diagnostic.render()),
+                    None => eprintln!("error: {e}"),
+                }
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if json {
+        println!("{}", Json::Array(shaders));
+    } else {
+        eprintln!("{} shaders ok", shaders.len());
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Collects all files below `root.join(dir)`, relative to `root`
+fn collect_files(root: &Path, dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(root.join(dir))? {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn shader_json(path: &Path, reflection: &ShaderReflection) -> Json {
+    let entry_points = Json::array(&reflection.entry_points, |ep| {
+        Json::Object(vec![
+            ("name", Json::string(&ep.name)),
+            ("stage", Json::string(stage_name(ep.stage))),
+            ("workgroup_size", Json::array(ep.workgroup_size, Json::from)),
+            (
+                "vertex_inputs",
+                Json::array(&ep.vertex_inputs, |input| {
+                    Json::Object(vec![
+                        ("location", input.location.into()),
+                        ("name", input.name.clone().into()),
+                        ("type", input.ty.to_string().into()),
+                    ])
+                }),
+            ),
+            (
+                "used_bindings",
+                Json::array(&ep.used_bindings, |&(group, binding)| {
+                    Json::Object(vec![("group", group.into()), ("binding", binding.into())])
+                }),
+            ),
+        ])
+    });
+
+    let bindings = Json::array(&reflection.bindings, |binding| {
+        Json::Object(vec![
+            ("group", binding.group.into()),
+            ("binding", binding.binding.into()),
+            ("name", binding.name.clone().into()),
+            ("type", binding_type_json(&binding.ty)),
+            ("count", binding.count.map(|count| count.get()).into()),
+        ])
+    });
+
+    Json::Object(vec![
+        ("path", path.display().to_string().into()),
+        ("entry_points", entry_points),
+        ("bindings", bindings),
+    ])
+}
+
+fn binding_type_json(ty: &wgpu::BindingType) -> Json {
+    match ty {
+        wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset,
+            min_binding_size,
+        } => {
+            let kind = match ty {
+                wgpu::BufferBindingType::Uniform => "uniform",
+                wgpu::BufferBindingType::Storage { read_only: true } => "read_only_storage",
+                wgpu::BufferBindingType::Storage { read_only: false } => "storage",
+            };
+            Json::Object(vec![
+                ("kind", Json::string("buffer")),
+                ("buffer_type", Json::string(kind)),
+                ("has_dynamic_offset", (*has_dynamic_offset).into()),
+                (
+                    "min_binding_size",
+                    min_binding_size.map(|size| size.get()).into(),
+                ),
+            ])
+        }
+        wgpu::BindingType::Sampler(ty) => {
+            let kind = match ty {
+                wgpu::SamplerBindingType::Filtering => "filtering",
+                wgpu::SamplerBindingType::NonFiltering => "non_filtering",
+                wgpu::SamplerBindingType::Comparison => "comparison",
+            };
+            Json::Object(vec![
+                ("kind", Json::string("sampler")),
+                ("sampler_type", Json::string(kind)),
+            ])
+        }
+        wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled,
+        } => {
+            let sample_type = match sample_type {
+                wgpu::TextureSampleType::Float { filterable: true } => "float",
+                wgpu::TextureSampleType::Float { filterable: false } => "unfilterable_float",
+                wgpu::TextureSampleType::Depth => "depth",
+                wgpu::TextureSampleType::Sint => "sint",
+                wgpu::TextureSampleType::Uint => "uint",
+            };
+            Json::Object(vec![
+                ("kind", Json::string("texture")),
+                ("sample_type", Json::string(sample_type)),
+                (
+                    "view_dimension",
+                    Json::string(dimension_name(*view_dimension)),
+                ),
+                ("multisampled", (*multisampled).into()),
+            ])
+        }
+        wgpu::BindingType::StorageTexture {
+            access,
+            format,
+            view_dimension,
+        } => {
+            let access = match access {
+                wgpu::StorageTextureAccess::ReadOnly => "read",
+                wgpu::StorageTextureAccess::WriteOnly => "write",
+                wgpu::StorageTextureAccess::ReadWrite => "read_write",
+            };
+            Json::Object(vec![
+                ("kind", Json::string("storage_texture")),
+                ("access", Json::string(access)),
+                ("format", Json::string(format!("{format:?}"))),
+                (
+                    "view_dimension",
+                    Json::string(dimension_name(*view_dimension)),
+                ),
+            ])
+        }
+    }
+}
+
+fn stage_name(stage: ShaderStage) -> &'static str {
+    match stage {
+        ShaderStage::Vertex => "vertex",
+        ShaderStage::Fragment => "fragment",
+        ShaderStage::Compute => "compute",
+    }
+}
+
+fn dimension_name(dimension: wgpu::TextureViewDimension) -> &'static str {
+    match dimension {
+        wgpu::TextureViewDimension::D1 => "1d",
+        wgpu::TextureViewDimension::D2 => "2d",
+        wgpu::TextureViewDimension::D2Array => "2d_array",
+        wgpu::TextureViewDimension::Cube => "cube",
+        wgpu::TextureViewDimension::CubeArray => "cube_array",
+        wgpu::TextureViewDimension::D3 => "3d",
+    }
+}
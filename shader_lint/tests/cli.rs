@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const VALID: &str = r"
+@group(0) @binding(1)
+var<storage, read_write> out: array<u32>;
+
+@compute @workgroup_size(8, 1, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    out[id.x] = id.x;
+}
+";
+
+const INVALID: &str = r"
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return colour;
+}
+";
+
+/// Writes `files` into a fresh directory below the target directory
+fn shader_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    for (path, source) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+
+    dir
+}
+
+fn shader_lint(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_shader_lint"))
+        .args(args)
+        .arg(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn valid_shaders_are_dumped_as_json() {
+    let dir = shader_dir(
+        "shader-lint-valid",
+        &[("fill.wgsl", VALID), ("README.md", "# Not a shader")],
+    );
+
+    let output = shader_lint(&["--json"], &dir);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        r#"[
+  {
+    "path": "fill.wgsl",
+    "entry_points": [
+      {
+        "name": "cs_main",
+        "stage": "compute",
+        "workgroup_size": [
+          8,
+          1,
+          1
+        ],
+        "vertex_inputs": [],
+        "used_bindings": [
+          {
+            "group": 0,
+            "binding": 1
+          }
+        ]
+      }
+    ],
+    "bindings": [
+      {
+        "group": 0,
+        "binding": 1,
+        "name": "out",
+        "type": {
+          "kind": "buffer",
+          "buffer_type": "storage",
+          "has_dynamic_offset": false,
+          "min_binding_size": null
+        },
+        "count": null
+      }
+    ]
+  }
+]
+"#
+    );
+
+    let output = shader_lint(&[], &dir);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "1 shaders ok\n");
+}
+
+#[test]
+fn invalid_shader_fails_with_its_diagnostic() {
+    let dir = shader_dir(
+        "shader-lint-invalid",
+        &[("fill.wgsl", VALID), ("lit.wgsl", INVALID)],
+    );
+
+    let output = shader_lint(&["--json"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty(), "no JSON is printed on errors");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "error: no definition in scope for identifier: 'colour'\n  \
+               ┌─ {}:4:12\n  \
+               │\n\
+             4 │     return colour;\n  \
+               │            ^^^^^^ unknown identifier\n",
+            dir.join("lit.wgsl").display()
+        )
+    );
+}
+
+#[test]
+fn unknown_arguments_print_the_usage() {
+    let dir = shader_dir("shader-lint-usage", &[]);
+
+    let output = shader_lint(&["--yaml"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("usage: shader_lint"));
+}